@@ -0,0 +1,74 @@
+//! Tracks the time needed to generate the different parts of an organic city.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+use std::time::Duration;
+
+use city_generation::CityGenerator;
+
+const SEED: u64 = 42;
+
+fn generator() -> CityGenerator {
+    CityGenerator::new(SEED, 8..30, 8..30, 20..100, 500)
+}
+
+fn important_buildings(c: &mut Criterion) {
+    let mut group = c.benchmark_group("important_buildings");
+    for important_buildings in [3, 6, 10] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(important_buildings),
+            &important_buildings,
+            |b, &important_buildings| {
+                b.iter(|| {
+                    generator().generate_important_buildings(black_box(important_buildings), 1.0)
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn building_placement(c: &mut Criterion) {
+    let mut group = c.benchmark_group("building_placement");
+    group.sample_size(10);
+    for buildings in [100, 500] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(buildings),
+            &buildings,
+            |b, &buildings| {
+                b.iter_batched(
+                    || {
+                        let mut city_gen = generator();
+                        let city = city_gen.generate_important_buildings(3, 1.0);
+                        (city_gen, city)
+                    },
+                    |(mut city_gen, mut city)| {
+                        city_gen.generate_buildings(&mut city, black_box(buildings));
+                        city
+                    },
+                    criterion::BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation");
+    // Every iteration builds a full city, a handful of samples over a longer time is plenty
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(30));
+    for buildings in [1_000, 10_000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(buildings),
+            &buildings,
+            |b, &buildings| {
+                b.iter(|| generator().generate(black_box(buildings), 3, 1.0));
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, important_buildings, building_placement, generation);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+//! Tracks the time needed to redraw the explorer's frame at several zoom levels, from scratch and
+//! when panning over cached tiles.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use city_generation::{graphics::CityExplorer, CityGenerator};
+
+const SEED: u64 = 7;
+const WINDOW_SIZE: (u32, u32) = (1280, 720);
+
+fn redraw_pixels(c: &mut Criterion) {
+    let city = CityGenerator::new(SEED, 8..30, 8..30, 20..100, 500).generate(300, 3, 1.0);
+    let center = (
+        (city.min_x + city.max_x) as f32 / 2.0,
+        (city.min_y + city.max_y) as f32 / 2.0,
+    );
+    let mut explorer = CityExplorer::new(city, WINDOW_SIZE);
+    explorer.camera.look_at(center);
+
+    let mut group = c.benchmark_group("redraw_pixels");
+    for zoom in [0.1, 0.5, 1.0, 4.0, 16.0] {
+        explorer.camera.set_zoom(zoom);
+        group.bench_with_input(BenchmarkId::new("refresh", zoom), &zoom, |b, _| {
+            b.iter(|| explorer.refresh());
+        });
+        // Back and forth, so the tiles stay the same
+        let mut step = 20.0 / zoom;
+        group.bench_with_input(BenchmarkId::new("pan", zoom), &zoom, |b, _| {
+            b.iter(|| {
+                let (x, y) = explorer.camera.center();
+                explorer.camera.look_at((x + step, y));
+                step = -step;
+                explorer.redraw_pixels();
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, redraw_pixels);
+criterion_main!(benches);
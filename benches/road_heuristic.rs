@@ -0,0 +1,72 @@
+//! Compares the road search heuristics and goal policies, both in time and in expanded nodes.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+
+use city_generation::{
+    building::Building, city::City, road_cost::CellKind, CityGenerator, DefaultRoadCost,
+    GoalPolicy, RoadCostModel,
+};
+
+const SEED: u64 = 42;
+const BUILDINGS: usize = 150;
+
+/// Default costs with the heuristic `generate_road` used to hardcode
+struct LegacyHeuristic(DefaultRoadCost);
+
+impl RoadCostModel for LegacyHeuristic {
+    fn step_cost(
+        &self,
+        city: &City,
+        from: (i32, i32),
+        to: (i32, i32),
+        kind: CellKind,
+    ) -> Option<i32> {
+        self.0.step_cost(city, from, to, kind)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn heuristic(&self, pos: (i32, i32), target: &Building, _goal: GoalPolicy) -> i32 {
+        let (x, y) = pos;
+        f64::from(((x - target.x + target.width).abs() + (y - target.y + target.height).abs()) * 10)
+            .sqrt() as i32
+    }
+}
+
+fn generator(legacy: bool, goal_policy: GoalPolicy) -> CityGenerator {
+    let road_cost: Box<dyn RoadCostModel> = if legacy {
+        Box::new(LegacyHeuristic(DefaultRoadCost::default()))
+    } else {
+        Box::new(DefaultRoadCost::default())
+    };
+    CityGenerator::new(SEED, 8..30, 8..30, 20..100, 500)
+        .with_road_cost(road_cost)
+        .with_goal_policy(goal_policy)
+}
+
+fn road_heuristic(c: &mut Criterion) {
+    let configurations = [
+        ("legacy_any_road", true, GoalPolicy::AnyRoad),
+        ("rectangle_any_road", false, GoalPolicy::AnyRoad),
+        ("legacy_target_door", true, GoalPolicy::TargetDoor),
+        ("rectangle_target_door", false, GoalPolicy::TargetDoor),
+    ];
+
+    let mut group = c.benchmark_group("road_heuristic");
+    group.sample_size(10);
+    for &(name, legacy, goal_policy) in &configurations {
+        // Generations are deterministic, the expanded nodes are reported once before measuring
+        let mut city_gen = generator(legacy, goal_policy);
+        city_gen.generate(BUILDINGS, 3, 1.0);
+        eprintln!(
+            "road_heuristic/{name}/{BUILDINGS}: {} expanded nodes",
+            city_gen.expanded_nodes()
+        );
+        group.bench_function(BenchmarkId::new(name, BUILDINGS), |b| {
+            b.iter(|| generator(legacy, goal_policy).generate(black_box(BUILDINGS), 3, 1.0));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, road_heuristic);
+criterion_main!(benches);
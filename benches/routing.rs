@@ -0,0 +1,78 @@
+//! Tracks the time needed by A* to route roads in an existing city.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+
+use city_generation::{Building, CityGenerator, GoalPolicy, Router};
+
+const SEED: u64 = 7;
+const BUILDINGS: usize = 300;
+
+fn road_routing(c: &mut Criterion) {
+    // Searches must reach the doors, otherwise they end right away on the building's own road
+    let mut city_gen = CityGenerator::new(SEED, 8..30, 8..30, 20..100, 500)
+        .with_goal_policy(GoalPolicy::TargetDoor);
+    let city = city_gen.generate(BUILDINGS, 3, 1.0);
+
+    // Route some buildings towards each important building
+    let important = city
+        .important_buildings()
+        .iter()
+        .map(|key| &city.buildings()[key])
+        .collect::<Vec<&Building>>();
+    let starts = city
+        .building_order()
+        .iter()
+        .step_by(10)
+        .map(|key| &city.buildings()[key])
+        .filter(|building| !building.is_important)
+        .collect::<Vec<&Building>>();
+
+    let mut group = c.benchmark_group("road_routing");
+    group.sample_size(10);
+    group.bench_function("to_important_buildings", |b| {
+        b.iter(|| {
+            for start in &starts {
+                for end in &important {
+                    black_box(city_gen.generate_road(&city, start, end));
+                }
+            }
+        });
+    });
+    group.finish();
+}
+
+/// Compares the routers on whole generations. Expanded nodes, coarse searches included, and total
+/// road length are reported once before measuring, to check the hierarchical router saves work
+/// without making roads much longer.
+fn routers(c: &mut Criterion) {
+    let configurations = [
+        ("astar", Router::AStar),
+        ("hierarchical_8", Router::Hierarchical { chunk_size: 8 }),
+        ("hierarchical_16", Router::Hierarchical { chunk_size: 16 }),
+        ("hierarchical_32", Router::Hierarchical { chunk_size: 32 }),
+    ];
+    let generator = |router| {
+        CityGenerator::new(SEED, 8..30, 8..30, 20..100, 500)
+            .with_goal_policy(GoalPolicy::TargetDoor)
+            .with_router(router)
+    };
+
+    let mut group = c.benchmark_group("routers");
+    group.sample_size(10);
+    for &(name, router) in &configurations {
+        let mut city_gen = generator(router);
+        let city = city_gen.generate(BUILDINGS, 3, 1.0);
+        eprintln!(
+            "routers/{name}/{BUILDINGS}: {} expanded nodes, {} road cells",
+            city_gen.expanded_nodes(),
+            city.roads().iter().map(Vec::len).sum::<usize>()
+        );
+        group.bench_function(BenchmarkId::new(name, BUILDINGS), |b| {
+            b.iter(|| generator(router).generate(black_box(BUILDINGS), 3, 1.0));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, road_routing, routers);
+criterion_main!(benches);
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use image::{Rgba, RgbaImage};
+
+use crate::{city::City, city_generation::CellType, road_network::RoadNetwork};
+
+/// Color of the buildings that can't be reached from any source
+const UNREACHABLE: [u8; 4] = [96, 96, 96, 255];
+
+/// Color of a value between 0 and 1, from green to yellow to red
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn heat_color(ratio: f32) -> [u8; 4] {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let (red, green) = if ratio < 0.5 {
+        (ratio * 2.0, 1.0)
+    } else {
+        (1.0, (1.0 - ratio) * 2.0)
+    };
+    [(red * 255.0) as u8, (green * 255.0) as u8, 0, 255]
+}
+
+/// How far every road cell and building is from the closest of a set of sources, walking on the
+/// roads
+pub struct AccessibilityMap {
+    /// Number of steps from the closest source to each reachable road cell and door
+    pub cell_distances: HashMap<(i32, i32), usize>,
+    /// Number of steps from the closest source to the door of each reachable building, by key
+    pub building_distances: HashMap<(i32, i32), usize>,
+    /// Largest distance of the map
+    pub max_distance: usize,
+}
+
+impl AccessibilityMap {
+    /// Travel distances from the given cells. Every step costing the same, a breadth-first
+    /// search gives the same result as a multi-source Dijkstra.
+    pub fn new(
+        city: &City,
+        network: &RoadNetwork,
+        sources: impl IntoIterator<Item = (i32, i32)>,
+    ) -> Self {
+        let cell_distances = network.distances(sources);
+        let building_distances = city
+            .buildings()
+            .iter()
+            .filter_map(|(key, b)| cell_distances.get(&b.door).map(|&d| (*key, d)))
+            .collect::<HashMap<_, _>>();
+        let max_distance = cell_distances.values().copied().max().unwrap_or(0);
+        Self {
+            cell_distances,
+            building_distances,
+            max_distance,
+        }
+    }
+
+    /// Travel distances from the doors of the important buildings
+    pub fn from_important_buildings(city: &City, network: &RoadNetwork) -> Self {
+        Self::new(
+            city,
+            network,
+            city.important_buildings()
+                .iter()
+                .map(|key| city.buildings()[key].door),
+        )
+    }
+
+    /// Color of a distance, from green for the closest to red for the farthest
+    #[allow(clippy::cast_precision_loss)]
+    pub fn color(&self, distance: usize) -> [u8; 4] {
+        heat_color(distance as f32 / self.max_distance.max(1) as f32)
+    }
+
+    /// Color of every road and building cell of the city
+    pub fn heatmap(&self, city: &City) -> HashMap<(i32, i32), [u8; 4]> {
+        let mut colors = HashMap::new();
+        for (key, building) in city.buildings() {
+            let color = self
+                .building_distances
+                .get(key)
+                .map_or(UNREACHABLE, |&d| self.color(d));
+            for x in building.x..=building.x + building.width {
+                for y in building.y..=building.y + building.height {
+                    colors.insert((x, y), color);
+                }
+            }
+        }
+        for (&cell, kind) in &city.is_something {
+            if let CellType::Road = kind {
+                let color = self
+                    .cell_distances
+                    .get(&cell)
+                    .map_or(UNREACHABLE, |&d| self.color(d));
+                colors.insert(cell, color);
+            }
+        }
+        colors
+    }
+
+    /// Render the heatmap with one pixel per cell, inside the borders of the city
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+    pub fn to_png(&self, city: &City) -> RgbaImage {
+        let colors = self.heatmap(city);
+        RgbaImage::from_fn(
+            (city.max_x - city.min_x).max(0) as u32,
+            (city.max_y - city.min_y).max(0) as u32,
+            |x, y| {
+                let cell = (city.min_x + x as i32, city.min_y + y as i32);
+                Rgba(colors.get(&cell).copied().unwrap_or([0, 0, 0, 255]))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{building::Building, city::RoadTier};
+
+    fn building(x: i32, is_important: bool) -> Building {
+        Building {
+            door: (x + 2, 4),
+            x,
+            y: 0,
+            width: 4,
+            height: 4,
+            is_important,
+            id: 0,
+        }
+    }
+
+    /// An important building and a house 10 steps apart along a road, and a house with no road
+    fn city() -> City {
+        let mut city = City::new();
+        city.insert_building(building(0, true));
+        city.insert_building(building(10, false));
+        city.insert_building(building(30, false));
+        let road = (4..=5)
+            .map(|y| (2, y))
+            .chain((3..=11).map(|x| (x, 5)))
+            .chain([(12, 5), (12, 4)]);
+        city.push_road(road.collect(), RoadTier::Local);
+        city.update_borders();
+        city.rebuild_occupancy();
+        city
+    }
+
+    #[test]
+    fn distances_are_counted_along_the_roads() {
+        let city = city();
+        let map = AccessibilityMap::from_important_buildings(&city, &RoadNetwork::new(&city));
+        assert_eq!(map.building_distances.get(&(0, 0)), Some(&0));
+        assert_eq!(map.building_distances.get(&(10, 0)), Some(&12));
+        assert_eq!(map.building_distances.get(&(30, 0)), None);
+        assert_eq!(map.max_distance, 12);
+        assert_eq!(map.cell_distances[&(7, 5)], 6);
+    }
+
+    #[test]
+    fn the_closest_source_gives_the_distance() {
+        let city = city();
+        let map = AccessibilityMap::new(&city, &RoadNetwork::new(&city), [(2, 4), (12, 4)]);
+        assert_eq!(map.building_distances[&(10, 0)], 0);
+        assert_eq!(map.cell_distances[&(7, 5)], 6);
+        assert_eq!(map.cell_distances[&(8, 5)], 5);
+        assert_eq!(map.max_distance, 6);
+    }
+
+    #[test]
+    fn unreachable_buildings_are_grey_in_the_heatmap() {
+        let city = city();
+        let map = AccessibilityMap::from_important_buildings(&city, &RoadNetwork::new(&city));
+        let colors = map.heatmap(&city);
+        assert_eq!(colors[&(31, 1)], UNREACHABLE);
+        assert_eq!(colors[&(1, 1)], heat_color(0.0));
+        assert_eq!(colors[&(11, 1)], heat_color(1.0));
+        assert_eq!(heat_color(0.0), [0, 255, 0, 255]);
+        assert_eq!(heat_color(1.0), [255, 0, 0, 255]);
+
+        let image = map.to_png(&city);
+        let size = (city.max_x - city.min_x, city.max_y - city.min_y);
+        assert_eq!(image.dimensions(), (size.0 as u32, size.1 as u32));
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::Serialize;
+
+use crate::{city::City, city_generation::CityGenerator, export};
+
+/// One city to generate in a batch
+#[derive(Clone, Debug)]
+pub struct BatchJob {
+    /// Seed of the generator
+    pub seed: u64,
+    /// Short description of the parameters, used to name the output and in the summary
+    pub parameters: String,
+    /// Number of normal buildings
+    pub buildings: usize,
+    /// Number of important buildings
+    pub important_buildings: usize,
+    /// Scale of the important buildings
+    pub scale: f64,
+}
+
+/// What came out of a job
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchSummary {
+    pub seed: u64,
+    pub parameters: String,
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+    pub buildings: usize,
+    pub roads: usize,
+    /// Number of steps of all the roads
+    pub road_length: usize,
+    /// Number of roads the generator failed to route
+    pub failed_roads: usize,
+    /// Generation time, in seconds
+    pub seconds: f64,
+    /// Where the city was saved
+    pub output: PathBuf,
+}
+
+impl BatchSummary {
+    fn new(
+        job: &BatchJob,
+        city: &City,
+        failed_roads: usize,
+        seconds: f64,
+        output: PathBuf,
+    ) -> Self {
+        Self {
+            seed: job.seed,
+            parameters: job.parameters.clone(),
+            min_x: city.min_x,
+            min_y: city.min_y,
+            max_x: city.max_x,
+            max_y: city.max_y,
+            buildings: city.buildings().len(),
+            roads: city.roads().len(),
+            road_length: city
+                .roads()
+                .iter()
+                .map(|road| road.len().saturating_sub(1))
+                .sum(),
+            failed_roads,
+            seconds,
+            output,
+        }
+    }
+}
+
+/// Generate every job in parallel with the generator built for it, saving each city in
+/// `output_dir`. The summaries are in the order of the jobs.
+pub fn run_batch(
+    jobs: &[BatchJob],
+    output_dir: &Path,
+    generator: impl Fn(&BatchJob) -> CityGenerator + Sync,
+) -> io::Result<Vec<BatchSummary>> {
+    fs::create_dir_all(output_dir)?;
+    jobs.par_iter()
+        .map(|job| {
+            let mut city_gen = generator(job);
+            let now = Instant::now();
+            let city = city_gen.generate(job.buildings, job.important_buildings, job.scale);
+            let seconds = now.elapsed().as_secs_f64();
+
+            let output = output_dir.join(format!("{}_seed{}.json", job.parameters, job.seed));
+            export::save_city(&city, &output)?;
+            log::info!("Generated {} in {seconds}", output.display());
+            Ok(BatchSummary::new(
+                job,
+                &city,
+                city_gen.failed_roads(),
+                seconds,
+                output,
+            ))
+        })
+        .collect()
+}
+
+/// Summaries as CSV, one line per job
+pub fn summary_csv(summaries: &[BatchSummary]) -> String {
+    let mut csv = String::from(
+        "seed,parameters,min_x,min_y,max_x,max_y,buildings,roads,road_length,failed_roads,seconds,output\n",
+    );
+    // Writing to a String can't fail, so the results are ignored
+    for s in summaries {
+        let _ = writeln!(
+            csv,
+            "{},\"{}\",{},{},{},{},{},{},{},{},{},\"{}\"",
+            s.seed,
+            s.parameters.replace('"', "\"\""),
+            s.min_x,
+            s.min_y,
+            s.max_x,
+            s.max_y,
+            s.buildings,
+            s.roads,
+            s.road_length,
+            s.failed_roads,
+            s.seconds,
+            s.output.display().to_string().replace('"', "\"\""),
+        );
+    }
+    csv
+}
+
+/// Summaries as a JSON array
+pub fn summary_json(summaries: &[BatchSummary]) -> String {
+    serde_json::to_string_pretty(summaries).expect("summaries can always be serialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(seed: u64, parameters: &str) -> BatchJob {
+        BatchJob {
+            seed,
+            parameters: parameters.to_string(),
+            buildings: 10,
+            important_buildings: 2,
+            scale: 1.0,
+        }
+    }
+
+    #[test]
+    fn every_job_is_generated_saved_and_summarized_in_order() {
+        let output_dir = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+        let jobs = [job(3, "small"), job(1, "small"), job(2, "say \"hi\"")];
+        let summaries = run_batch(&jobs, &output_dir, |job| {
+            CityGenerator::new(job.seed, 8..30, 8..30, 20..100, 500)
+        })
+        .unwrap();
+
+        assert_eq!(
+            summaries.iter().map(|s| s.seed).collect::<Vec<_>>(),
+            [3, 1, 2]
+        );
+        for summary in &summaries {
+            let city = export::load_city(&summary.output).unwrap();
+            assert_eq!(city.buildings().len(), summary.buildings);
+            assert_eq!(summary.buildings, 12);
+            assert_eq!(city.roads().len(), summary.roads);
+        }
+        // The same seed gives the same city
+        let again = run_batch(&jobs[..1], &output_dir, |job| {
+            CityGenerator::new(job.seed, 8..30, 8..30, 20..100, 500)
+        })
+        .unwrap();
+        assert_eq!(again[0].road_length, summaries[0].road_length);
+        fs::remove_dir_all(&output_dir).unwrap();
+
+        let csv = summary_csv(&summaries);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("seed,parameters,"));
+        assert!(lines[3].starts_with("2,\"say \"\"hi\"\"\","));
+        let json: Vec<serde_json::Value> = serde_json::from_str(&summary_json(&summaries)).unwrap();
+        assert_eq!(json.len(), 3);
+        assert_eq!(json[1]["seed"], 1);
+    }
+}
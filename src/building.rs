@@ -1,8 +1,9 @@
 use rand::Rng;
 use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
 /// Building of the city
-#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq)]
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq, Serialize, Deserialize)]
 pub struct Building {
     /// Coordinates of the door
     pub door: (i32, i32),
@@ -33,6 +34,14 @@ impl Building {
         x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
     }
 
+    /// Manhattan distance from a point to the building, 0 if the point is inside
+    pub fn distance(&self, pos: (i32, i32)) -> i32 {
+        let (x, y) = pos;
+        let dx = (self.x - x).max(x - (self.x + self.width)).max(0);
+        let dy = (self.y - y).max(y - (self.y + self.height)).max(0);
+        dx + dy
+    }
+
     /// Create a building from a rectangle and ID, randomizes the door
     pub fn with_random_door(
         rng: &mut ChaCha8Rng,
@@ -0,0 +1,368 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    city::{City, RoadTier},
+    city_generation::CellType,
+    road_network::RoadNetwork,
+};
+
+/// Stretch of road between two nodes of the network (intersections, dead ends and doors)
+#[derive(Clone, Debug)]
+pub struct Segment {
+    /// Cells of the segment, from one end node to the other, both included
+    pub cells: Vec<(i32, i32)>,
+    /// Indices of the end nodes
+    pub ends: (usize, usize),
+}
+
+impl Segment {
+    /// Number of steps along the segment
+    pub fn length(&self) -> usize {
+        self.cells.len() - 1
+    }
+}
+
+/// Move from a cell to the next one
+type Step = ((i32, i32), (i32, i32));
+
+/// Follow every chain of cells leaving `start` that wasn't walked yet, until the next node
+fn walk(
+    network: &RoadNetwork,
+    start: (i32, i32),
+    node_index: &HashMap<(i32, i32), usize>,
+    walked: &mut HashSet<Step>,
+    segments: &mut Vec<Vec<(i32, i32)>>,
+) {
+    for &first in network.neighbors(start) {
+        if walked.contains(&(start, first)) {
+            continue;
+        }
+        let mut segment = vec![start, first];
+        walked.insert((start, first));
+        walked.insert((first, start));
+        while !node_index.contains_key(&segment[segment.len() - 1]) {
+            let (previous, current) = (segment[segment.len() - 2], segment[segment.len() - 1]);
+            // Cells that aren't nodes have exactly two neighbors
+            let next =
+                network.neighbors(current)[usize::from(network.neighbors(current)[0] == previous)];
+            walked.insert((current, next));
+            walked.insert((next, current));
+            segment.push(next);
+        }
+        segments.push(segment);
+    }
+}
+
+/// Road network where the chains of cells between two nodes are merged into segments
+pub struct SegmentGraph {
+    /// Cells where the network branches or ends
+    pub nodes: Vec<(i32, i32)>,
+    pub segments: Vec<Segment>,
+    /// Segments leaving each node, with the node at their other end
+    adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+impl SegmentGraph {
+    pub fn new(network: &RoadNetwork) -> Self {
+        let mut cells = network.cells().collect::<Vec<_>>();
+        // Sorted so the indices don't depend on the order of the hash map
+        cells.sort_unstable();
+        let mut node_index = cells
+            .iter()
+            .filter(|&&cell| network.neighbors(cell).len() != 2)
+            .enumerate()
+            .map(|(i, &cell)| (cell, i))
+            .collect::<HashMap<_, _>>();
+        let mut nodes = vec![(0, 0); node_index.len()];
+        for (&cell, &i) in &node_index {
+            nodes[i] = cell;
+        }
+
+        let mut segments = vec![];
+        let mut walked = HashSet::new();
+        for &node in &nodes {
+            walk(network, node, &node_index, &mut walked, &mut segments);
+        }
+        // Loops without any node, made one by picking one of their cells
+        for &cell in &cells {
+            if !node_index.contains_key(&cell)
+                && network
+                    .neighbors(cell)
+                    .iter()
+                    .any(|&n| !walked.contains(&(cell, n)))
+            {
+                node_index.insert(cell, nodes.len());
+                nodes.push(cell);
+                walk(network, cell, &node_index, &mut walked, &mut segments);
+            }
+        }
+
+        let segments = segments
+            .into_iter()
+            .map(|cells| Segment {
+                ends: (node_index[&cells[0]], node_index[cells.last().unwrap()]),
+                cells,
+            })
+            .collect::<Vec<_>>();
+        let mut adjacency = vec![vec![]; nodes.len()];
+        for (i, segment) in segments.iter().enumerate() {
+            adjacency[segment.ends.0].push((i, segment.ends.1));
+            adjacency[segment.ends.1].push((i, segment.ends.0));
+        }
+        Self {
+            nodes,
+            segments,
+            adjacency,
+        }
+    }
+}
+
+/// Centrality of every segment of a [`SegmentGraph`], in its order
+#[derive(Clone, Debug)]
+pub struct Centrality {
+    /// Share of the shortest paths between two nodes going through the segment, relative to the
+    /// busiest segment
+    pub betweenness: Vec<f64>,
+    /// Average closeness of the two ends of the segment, the inverse of their average distance
+    /// to the nodes they can reach, relative to the most central segment
+    pub closeness: Vec<f64>,
+}
+
+/// Shortest paths from `source`, Brandes' accumulation giving the part of the betweenness of
+/// every segment coming from the paths starting there, and the sum of the distances
+#[allow(clippy::cast_precision_loss)]
+fn single_source(graph: &SegmentGraph, source: usize) -> (Vec<f64>, usize, usize) {
+    let n = graph.nodes.len();
+    let mut distance = vec![usize::MAX; n];
+    let mut paths = vec![0.0; n];
+    let mut predecessors: Vec<Vec<(usize, usize)>> = vec![vec![]; n];
+    let mut order = vec![];
+    let mut queue = BinaryHeap::new();
+    distance[source] = 0;
+    paths[source] = 1.0;
+    queue.push(Reverse((0, source)));
+    while let Some(Reverse((d, node))) = queue.pop() {
+        if d > distance[node] {
+            continue;
+        }
+        order.push(node);
+        for &(segment, next) in &graph.adjacency[node] {
+            let next_distance = d + graph.segments[segment].length();
+            if next_distance < distance[next] {
+                distance[next] = next_distance;
+                paths[next] = paths[node];
+                predecessors[next] = vec![(node, segment)];
+                queue.push(Reverse((next_distance, next)));
+            } else if next_distance == distance[next] && next != node {
+                paths[next] += paths[node];
+                predecessors[next].push((node, segment));
+            }
+        }
+    }
+
+    let mut betweenness = vec![0.0; graph.segments.len()];
+    let mut dependency = vec![0.0; n];
+    for &node in order.iter().rev() {
+        for &(previous, segment) in &predecessors[node] {
+            let share = paths[previous] / paths[node] * (1.0 + dependency[node]);
+            betweenness[segment] += share;
+            dependency[previous] += share;
+        }
+    }
+    let reached = order.len() - 1;
+    let total_distance = order.iter().map(|&node| distance[node]).sum();
+    (betweenness, reached, total_distance)
+}
+
+/// Betweenness and closeness centrality of the segments
+#[allow(clippy::cast_precision_loss)]
+pub fn centrality(graph: &SegmentGraph) -> Centrality {
+    let add = |mut total: Vec<f64>, values: &[f64]| {
+        for (total, value) in total.iter_mut().zip(values) {
+            *total += value;
+        }
+        total
+    };
+    let (betweenness, node_closeness) = (0..graph.nodes.len())
+        .into_par_iter()
+        .fold(
+            || {
+                (
+                    vec![0.0; graph.segments.len()],
+                    vec![0.0; graph.nodes.len()],
+                )
+            },
+            |(betweenness, mut closeness), source| {
+                let (partial, reached, total_distance) = single_source(graph, source);
+                if total_distance > 0 {
+                    closeness[source] = reached as f64 / total_distance as f64;
+                }
+                (add(betweenness, &partial), closeness)
+            },
+        )
+        .reduce(
+            || {
+                (
+                    vec![0.0; graph.segments.len()],
+                    vec![0.0; graph.nodes.len()],
+                )
+            },
+            |(b1, c1), (b2, c2)| (add(b1, &b2), add(c1, &c2)),
+        );
+    let closeness = graph
+        .segments
+        .iter()
+        .map(|segment| (node_closeness[segment.ends.0] + node_closeness[segment.ends.1]) / 2.0)
+        .collect::<Vec<f64>>();
+
+    let normalize = |values: Vec<f64>| {
+        let max = values.iter().copied().fold(0.0, f64::max);
+        if max > 0.0 {
+            values.into_iter().map(|v| v / max).collect()
+        } else {
+            values
+        }
+    };
+    Centrality {
+        betweenness: normalize(betweenness),
+        closeness: normalize(closeness),
+    }
+}
+
+/// Highest betweenness of the segments going through each cell
+pub fn cell_betweenness(graph: &SegmentGraph, centrality: &Centrality) -> HashMap<(i32, i32), f64> {
+    let mut scores: HashMap<(i32, i32), f64> = HashMap::new();
+    for (segment, &score) in graph.segments.iter().zip(&centrality.betweenness) {
+        for &cell in &segment.cells {
+            let entry = scores.entry(cell).or_default();
+            *entry = entry.max(score);
+        }
+    }
+    scores
+}
+
+/// Make arterials out of the busiest roads, the `share` of the roads whose busiest segment has
+/// the highest betweenness, and widen them with a second lane where there is room
+pub fn promote_main_streets(city: &mut City, share: f64) {
+    let graph = SegmentGraph::new(&RoadNetwork::new(city));
+    let scores = cell_betweenness(&graph, &centrality(&graph));
+    let mut ranked = city
+        .roads()
+        .iter()
+        .enumerate()
+        .map(|(i, road)| {
+            let score = road
+                .iter()
+                .filter_map(|cell| scores.get(cell))
+                .copied()
+                .fold(0.0, f64::max);
+            (i, score)
+        })
+        .collect::<Vec<(usize, f64)>>();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    let promoted = (ranked.len() as f64 * share.clamp(0.0, 1.0)).round() as usize;
+
+    let doors = city
+        .buildings()
+        .values()
+        .map(|b| b.door)
+        .collect::<HashSet<_>>();
+    let mut lanes = vec![];
+    for &(i, _) in &ranked[..promoted] {
+        city.set_road_tier(i, RoadTier::Arterial);
+        // The road shifted diagonally stays continuous and runs along it, cut where it would go
+        // through a building
+        let mut lane = vec![];
+        for &(x, y) in &city.roads()[i] {
+            let cell = (x + 1, y + 1);
+            if matches!(city.is_something.get(&cell), Some(CellType::Building))
+                || doors.contains(&cell)
+            {
+                if lane.len() > 1 {
+                    lanes.push(std::mem::take(&mut lane));
+                }
+                lane.clear();
+            } else {
+                lane.push(cell);
+            }
+        }
+        if lane.len() > 1 {
+            lanes.push(lane);
+        }
+    }
+    for lane in lanes {
+        for &cell in &lane {
+            city.is_something.insert(cell, CellType::Road);
+        }
+        city.push_road(lane, RoadTier::Arterial);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(roads: &[&[(i32, i32)]]) -> SegmentGraph {
+        let mut city = City::new();
+        for road in roads {
+            city.push_road(road.to_vec(), RoadTier::Local);
+        }
+        SegmentGraph::new(&RoadNetwork::new(&city))
+    }
+
+    /// Index of the segment going through a cell
+    fn segment_at(graph: &SegmentGraph, cell: (i32, i32)) -> usize {
+        graph
+            .segments
+            .iter()
+            .position(|segment| segment.cells.contains(&cell))
+            .unwrap()
+    }
+
+    #[test]
+    fn arms_of_a_star_are_equally_central() {
+        let graph = graph(&[
+            &[(0, 0), (1, 0), (2, 0)],
+            &[(0, 0), (-1, 0), (-2, 0)],
+            &[(0, 0), (0, 1), (0, 2)],
+            &[(0, 0), (0, -1), (0, -2)],
+        ]);
+        assert_eq!(graph.nodes.len(), 5);
+        assert_eq!(graph.segments.len(), 4);
+        let centrality = centrality(&graph);
+        assert!(centrality.betweenness.iter().all(|&b| b == 1.0));
+        assert!(centrality.closeness.iter().all(|&c| c == 1.0));
+    }
+
+    #[test]
+    fn middle_of_a_comb_is_the_busiest() {
+        // A street with a branch at x = 2 and another at x = 4
+        let graph = graph(&[
+            &[(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0)],
+            &[(2, 1), (2, 2)],
+            &[(4, 1), (4, 2)],
+        ]);
+        assert_eq!(graph.nodes.len(), 6);
+        assert_eq!(graph.segments.len(), 5);
+        let centrality = centrality(&graph);
+        let middle = segment_at(&graph, (3, 0));
+        let end = segment_at(&graph, (1, 0));
+        let branch = segment_at(&graph, (2, 2));
+        // 9 pairs of nodes on both sides go through the middle, 5 through each end
+        assert_eq!(centrality.betweenness[middle], 1.0);
+        assert!((centrality.betweenness[end] - 5.0 / 9.0).abs() < 1e-9);
+        assert!((centrality.betweenness[branch] - 5.0 / 9.0).abs() < 1e-9);
+        assert_eq!(centrality.closeness[middle], 1.0);
+        assert!(centrality.closeness[end] < 1.0);
+    }
+}
@@ -1,16 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{building::Building, city_generation::CellType};
 const CITY_BOUNDS_OFFSET: i32 = 20;
+/// Size of the squares used to index buildings by position
+const INDEX_CELL_SIZE: i32 = 64;
+
+/// Importance of a road in the network
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub enum RoadTier {
+    /// Links between important buildings, and avenues
+    Arterial,
+    /// Streets of a planned grid
+    Street,
+    /// Access roads from a building to the rest of the network
+    #[default]
+    Local,
+}
 
 /// Represents a city with buildings and roads.
+///
+/// Buildings and roads are only changed through its methods, which keep the order and index of
+/// the buildings up to date, and a tier for every road.
+#[derive(Clone)]
 pub struct City {
     /// Buildings of the city
-    pub buildings: HashMap<(i32, i32), Building>,
-    /// Buildings of the city
-    pub important_buildings: Vec<(i32, i32)>,
+    buildings: HashMap<(i32, i32), Building>,
+    /// Keys of the important buildings, in insertion order
+    important_buildings: Vec<(i32, i32)>,
     /// Roads of the city
-    pub roads: Vec<Vec<(i32, i32)>>,
+    roads: Vec<Vec<(i32, i32)>>,
+    /// Tier of each road, in the order of `roads`, only changed along with them
+    road_tiers: Vec<RoadTier>,
     /// x coordinate of the leftmost building
     pub min_x: i32,
     /// y coordinate of the topmost building
@@ -20,6 +44,10 @@ pub struct City {
     /// y coordinate of the bottommost building
     pub max_y: i32,
     pub is_something: HashMap<(i32, i32), CellType>,
+    /// Keys of the buildings, in insertion order
+    building_order: Vec<(i32, i32)>,
+    /// Keys of the buildings overlapping each square of the index
+    spatial_index: HashMap<(i32, i32), Vec<(i32, i32)>>,
 }
 
 impl Default for City {
@@ -32,7 +60,10 @@ impl Default for City {
             buildings: HashMap::new(),
             important_buildings: vec![],
             roads: vec![],
+            road_tiers: vec![],
             is_something: HashMap::new(),
+            building_order: vec![],
+            spatial_index: HashMap::new(),
         }
     }
 }
@@ -40,6 +71,195 @@ impl City {
     pub fn new() -> Self {
         City::default()
     }
+    /// Squares of the index covered by a rectangle, walls included
+    fn index_cells(x: i32, y: i32, width: i32, height: i32) -> impl Iterator<Item = (i32, i32)> {
+        let (x0, x1) = (
+            x.div_euclid(INDEX_CELL_SIZE),
+            (x + width).div_euclid(INDEX_CELL_SIZE),
+        );
+        let (y0, y1) = (
+            y.div_euclid(INDEX_CELL_SIZE),
+            (y + height).div_euclid(INDEX_CELL_SIZE),
+        );
+        (x0..=x1).flat_map(move |ix| (y0..=y1).map(move |iy| (ix, iy)))
+    }
+
+    /// Buildings of the city, by the position of their top left corner
+    pub fn buildings(&self) -> &HashMap<(i32, i32), Building> {
+        &self.buildings
+    }
+
+    /// Keys of the important buildings, in insertion order
+    pub fn important_buildings(&self) -> &[(i32, i32)] {
+        &self.important_buildings
+    }
+
+    /// Keys of the buildings, in insertion order
+    pub fn building_order(&self) -> &[(i32, i32)] {
+        &self.building_order
+    }
+
+    /// Add a building to the city, keeping track of its order, importance and position
+    pub fn insert_building(&mut self, building: Building) {
+        let key = (building.x, building.y);
+        for cell in Self::index_cells(building.x, building.y, building.width, building.height) {
+            self.spatial_index.entry(cell).or_default().push(key);
+        }
+        if building.is_important {
+            self.important_buildings.push(key);
+        }
+        self.building_order.push(key);
+        self.update_borders_from_new_building(&building);
+        self.buildings.insert(key, building);
+    }
+
+    /// Remove a building from the city, its order, importance and position. The borders and the
+    /// occupancy map are left as they are.
+    pub fn remove_building(&mut self, key: (i32, i32)) -> Option<Building> {
+        let building = self.buildings.remove(&key)?;
+        self.unindex(&building);
+        self.building_order.retain(|&k| k != key);
+        self.important_buildings.retain(|&k| k != key);
+        Some(building)
+    }
+
+    /// Put a building in the place of the one with the given key, keeping its order. The borders
+    /// and the occupancy map are left as they are. Returns the building replaced, if any.
+    pub fn replace_building(&mut self, key: (i32, i32), building: Building) -> Option<Building> {
+        let old = self.buildings.remove(&key)?;
+        self.unindex(&old);
+        let new_key = (building.x, building.y);
+        for k in self
+            .building_order
+            .iter_mut()
+            .chain(&mut self.important_buildings)
+        {
+            if *k == key {
+                *k = new_key;
+            }
+        }
+        for cell in Self::index_cells(building.x, building.y, building.width, building.height) {
+            self.spatial_index.entry(cell).or_default().push(new_key);
+        }
+        self.buildings.insert(new_key, building);
+        Some(old)
+    }
+
+    /// Replace every building by what `f` makes of it, keeping their order. Fails with the
+    /// corner two buildings would share, leaving the city as it was.
+    pub fn map_buildings(
+        &mut self,
+        mut f: impl FnMut(&Building) -> Building,
+    ) -> Result<(), (i32, i32)> {
+        let mapped = self
+            .building_order
+            .iter()
+            .map(|key| f(&self.buildings[key]))
+            .collect::<Vec<_>>();
+        let mut corners = HashSet::new();
+        if let Some(b) = mapped.iter().find(|b| !corners.insert((b.x, b.y))) {
+            return Err((b.x, b.y));
+        }
+        self.buildings.clear();
+        self.building_order.clear();
+        self.important_buildings.clear();
+        self.spatial_index.clear();
+        for building in mapped {
+            self.insert_building(building);
+        }
+        Ok(())
+    }
+
+    /// Remove a building from the squares of the index it overlaps
+    fn unindex(&mut self, building: &Building) {
+        let key = (building.x, building.y);
+        for cell in Self::index_cells(building.x, building.y, building.width, building.height) {
+            if let Some(keys) = self.spatial_index.get_mut(&cell) {
+                keys.retain(|&k| k != key);
+            }
+        }
+    }
+
+    /// Roads of the city
+    pub fn roads(&self) -> &[Vec<(i32, i32)>] {
+        &self.roads
+    }
+
+    /// Tier of each road, in the order of the roads
+    pub fn road_tiers(&self) -> &[RoadTier] {
+        &self.road_tiers
+    }
+
+    /// Roads of the city with their tier
+    pub fn roads_with_tiers(&self) -> impl Iterator<Item = (&Vec<(i32, i32)>, RoadTier)> {
+        self.roads.iter().zip(self.road_tiers.iter().copied())
+    }
+
+    /// Add a road to the city, with its tier
+    pub fn push_road(&mut self, road: Vec<(i32, i32)>, tier: RoadTier) {
+        self.roads.push(road);
+        self.road_tiers.push(tier);
+    }
+
+    /// Change the tier of the road at `index`
+    pub fn set_road_tier(&mut self, index: usize, tier: RoadTier) {
+        self.road_tiers[index] = tier;
+    }
+
+    /// Remove every road from the city, with their tiers
+    pub fn take_roads(&mut self) -> Vec<(Vec<(i32, i32)>, RoadTier)> {
+        let roads = std::mem::take(&mut self.roads);
+        roads
+            .into_iter()
+            .zip(std::mem::take(&mut self.road_tiers))
+            .collect()
+    }
+
+    /// Replace every road by what `f` makes of it, keeping their tiers
+    pub fn map_roads(&mut self, mut f: impl FnMut(&[(i32, i32)]) -> Vec<(i32, i32)>) {
+        for road in &mut self.roads {
+            *road = f(road);
+        }
+    }
+
+    /// Buildings that may be closer than `offset` to the given rectangle
+    pub fn buildings_near(
+        &self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        offset: i32,
+    ) -> impl Iterator<Item = &Building> {
+        Self::index_cells(
+            x - offset,
+            y - offset,
+            width + 2 * offset,
+            height + 2 * offset,
+        )
+        .filter_map(|cell| self.spatial_index.get(&cell))
+        .flatten()
+        .filter_map(|key| self.buildings.get(key))
+    }
+
+    /// Rebuild the occupancy map from the buildings and the roads, roads going over walls
+    pub fn rebuild_occupancy(&mut self) {
+        self.is_something.clear();
+        for building in self.buildings.values() {
+            for x in building.x..=building.x + building.width {
+                for y in building.y..=building.y + building.height {
+                    self.is_something.insert((x, y), CellType::Building);
+                }
+            }
+            self.is_something.remove(&building.door);
+        }
+        for road in &self.roads {
+            for &cell in road {
+                self.is_something.insert(cell, CellType::Road);
+            }
+        }
+    }
+
     /// Computes the borders of the city
     pub fn update_borders(&mut self) {
         self.min_x = self.buildings.values().map(|b| b.x).min().unwrap() - CITY_BOUNDS_OFFSET;
@@ -73,4 +293,50 @@ impl City {
             .max_y
             .max(building.y + building.height + CITY_BOUNDS_OFFSET);
     }
+
+    /// Update the borders of the city based on a new road
+    pub fn update_borders_from_new_road(&mut self, road: &[(i32, i32)]) {
+        for &(x, y) in road {
+            self.min_x = self.min_x.min(x - CITY_BOUNDS_OFFSET);
+            self.min_y = self.min_y.min(y - CITY_BOUNDS_OFFSET);
+            self.max_x = self.max_x.max(x + CITY_BOUNDS_OFFSET);
+            self.max_y = self.max_y.max(y + CITY_BOUNDS_OFFSET);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn building(x: i32, y: i32, is_important: bool) -> Building {
+        Building {
+            door: (x + 1, y),
+            x,
+            y,
+            width: 4,
+            height: 4,
+            is_important,
+            id: 0,
+        }
+    }
+
+    #[test]
+    fn replaced_and_removed_buildings_leave_the_index() {
+        let mut city = City::new();
+        city.insert_building(building(0, 0, true));
+        city.insert_building(building(100, 100, false));
+
+        city.replace_building((0, 0), building(200, 0, true));
+        assert_eq!(city.building_order(), [(200, 0), (100, 100)]);
+        assert_eq!(city.important_buildings(), [(200, 0)]);
+        assert_eq!(city.buildings_near(0, 0, 4, 4, 0).count(), 0);
+        assert_eq!(city.buildings_near(200, 0, 4, 4, 0).count(), 1);
+
+        assert!(city.remove_building((200, 0)).is_some());
+        assert!(city.remove_building((200, 0)).is_none());
+        assert!(city.important_buildings().is_empty());
+        assert_eq!(city.buildings_near(200, 0, 4, 4, 0).count(), 0);
+        assert_eq!(city.building_order(), [(100, 100)]);
+    }
 }
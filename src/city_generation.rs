@@ -1,10 +1,35 @@
 use pathfinding::prelude::astar;
-use rand::{seq::IteratorRandom, Rng, SeedableRng};
+use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
-use rayon::slice::ParallelSliceMut;
-use std::{collections::HashMap, ops::Range, time::Instant};
-
-use crate::{building::Building, city::City};
+use rayon::{
+    iter::{IntoParallelRefIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use crate::{
+    building::Building,
+    centrality,
+    city::{City, RoadTier},
+    network::ImportantNetwork,
+    placement::{DecayingNeighborPlacement, PlacementStrategy},
+    road_cost::{CellKind, DefaultRoadCost, GoalPolicy, Heading, RoadCostModel, RoadNode},
+    scaling,
+    straighten::straighten_road,
+};
+
+mod grid;
+mod hierarchical;
+
+pub use grid::GridLayout;
+pub use hierarchical::Router;
+
+use hierarchical::chunk_of;
 
 #[derive(Clone)]
 pub enum CellType {
@@ -12,6 +37,16 @@ pub enum CellType {
     Building,
 }
 
+/// Overall shape of the generated city
+#[derive(Clone, Debug, Default)]
+pub enum LayoutStrategy {
+    /// Buildings grow organically around the important buildings
+    #[default]
+    Organic,
+    /// Planned city with an orthogonal street grid and plazas for the important buildings
+    Grid(GridLayout),
+}
+
 /// Random city generator
 pub struct CityGenerator {
     rng: ChaCha8Rng,
@@ -21,10 +56,30 @@ pub struct CityGenerator {
     width_bound: Range<i32>,
     /// Min and max height of the buildings
     height_bound: Range<i32>,
-    /// Min and max distance between buildings
-    distance_bound: Range<i32>,
+    /// Where to put the normal buildings
+    placement: Box<dyn PlacementStrategy>,
+    /// Movement costs used when routing roads
+    road_cost: Box<dyn RoadCostModel>,
+    /// Redraw the stair-stepped parts of the roads as straight runs
+    straighten_roads: bool,
+    /// Where road searches may stop
+    goal_policy: GoalPolicy,
+    /// Which important buildings are linked together
+    important_network: ImportantNetwork,
+    /// Route the roads between important buildings in parallel
+    parallel_important_roads: bool,
+    /// Algorithm routing the roads
+    router: Router,
+    /// Share of the roads turned into widened arterials, the busiest ones, if any
+    main_streets: Option<f64>,
+    /// Number of nodes expanded by the road searches so far, chunks of the coarse searches included
+    expanded_nodes: AtomicUsize,
+    /// Number of road searches that found no road so far
+    failed_roads: AtomicUsize,
     /// Max distance between important buildings
     important_buildings_max_distance: i32,
+    /// Layout of the city
+    layout: LayoutStrategy,
 }
 
 impl CityGenerator {
@@ -39,44 +94,165 @@ impl CityGenerator {
         Self {
             rng: ChaCha8Rng::seed_from_u64(seed),
             is_something: HashMap::new(),
+            placement: Box::new(DecayingNeighborPlacement {
+                width_bound: width_bound.clone(),
+                height_bound: height_bound.clone(),
+                distance_bound,
+            }),
+            road_cost: Box::new(DefaultRoadCost::default()),
+            straighten_roads: false,
+            goal_policy: GoalPolicy::default(),
+            important_network: ImportantNetwork::default(),
+            parallel_important_roads: false,
+            router: Router::default(),
+            main_streets: None,
+            expanded_nodes: AtomicUsize::new(0),
+            failed_roads: AtomicUsize::new(0),
             width_bound,
             height_bound,
-            distance_bound,
             important_buildings_max_distance,
+            layout: LayoutStrategy::default(),
+        }
+    }
+    /// Use another placement strategy for the normal buildings than the default one
+    #[must_use]
+    pub fn with_placement(self, placement: Box<dyn PlacementStrategy>) -> Self {
+        Self { placement, ..self }
+    }
+    /// Use another cost model than the default one to route roads
+    #[must_use]
+    pub fn with_road_cost(self, road_cost: Box<dyn RoadCostModel>) -> Self {
+        Self { road_cost, ..self }
+    }
+    /// Straighten the roads once they have been routed
+    #[must_use]
+    pub fn with_straightened_roads(self, straighten_roads: bool) -> Self {
+        Self {
+            straighten_roads,
+            ..self
+        }
+    }
+    /// Choose where road searches may stop
+    #[must_use]
+    pub fn with_goal_policy(self, goal_policy: GoalPolicy) -> Self {
+        Self {
+            goal_policy,
+            ..self
+        }
+    }
+    /// Choose which important buildings get linked by a road
+    #[must_use]
+    pub fn with_important_network(self, important_network: ImportantNetwork) -> Self {
+        Self {
+            important_network,
+            ..self
+        }
+    }
+    /// Route the roads between important buildings in parallel. They are faster to generate but
+    /// can't follow each other, so the network has more parallel roads.
+    #[must_use]
+    pub fn with_parallel_important_roads(self, parallel_important_roads: bool) -> Self {
+        Self {
+            parallel_important_roads,
+            ..self
+        }
+    }
+    /// Choose the algorithm routing the roads, chunks smaller than 2 cells being enlarged
+    #[must_use]
+    pub fn with_router(self, router: Router) -> Self {
+        Self {
+            router: router.validated(),
+            ..self
+        }
+    }
+    /// Turn the given share of the roads, the busiest ones by betweenness centrality, into
+    /// widened arterials once the city is generated
+    #[must_use]
+    pub fn with_main_streets(self, main_streets: Option<f64>) -> Self {
+        Self {
+            main_streets,
+            ..self
         }
     }
+    /// Number of nodes expanded by all the road searches run by this generator, chunks of the
+    /// coarse searches included
+    pub fn expanded_nodes(&self) -> usize {
+        self.expanded_nodes.load(Ordering::Relaxed)
+    }
+    /// Number of roads this generator failed to route
+    pub fn failed_roads(&self) -> usize {
+        self.failed_roads.load(Ordering::Relaxed)
+    }
+    /// Use another layout strategy than the default organic one
+    #[must_use]
+    pub fn with_layout(self, layout: LayoutStrategy) -> Self {
+        Self { layout, ..self }
+    }
     pub fn generate(
         &mut self,
         normal_buildings: usize,
         important_buildings: usize,
-        important_building_scale: i32,
+        important_building_scale: f64,
     ) -> City {
-        println!("Generating important buildings");
+        if let LayoutStrategy::Grid(layout) = self.layout.clone() {
+            log::info!("Generating grid city");
+            let now = Instant::now();
+            let mut city = self.generate_grid(&layout, normal_buildings, important_buildings);
+            log::info!(
+                "Generated {} buildings in {}",
+                city.buildings().len(),
+                now.elapsed().as_secs_f32()
+            );
+            if !city.buildings().is_empty() {
+                city.update_borders();
+            }
+            city.is_something = self.is_something.clone();
+            self.promote_main_streets(&mut city);
+            return city;
+        }
+        log::info!("Generating important buildings");
 
         let now = Instant::now();
         let mut city =
             self.generate_important_buildings(important_buildings, important_building_scale);
         let duration = now.elapsed();
-        println!(
+        log::info!(
             "Generated {} important buildings in {}",
             important_buildings,
             duration.as_secs_f32()
         );
 
-        println!("Generating normal buildings");
+        log::info!("Generating normal buildings");
         let now = Instant::now();
         self.generate_buildings(&mut city, normal_buildings);
         let duration = now.elapsed();
-        println!(
+        log::info!(
             "Generated {} buildings in {}",
             normal_buildings,
             duration.as_secs_f32()
         );
-        city.update_borders();
+        if !city.buildings().is_empty() {
+            city.update_borders();
+        }
         city.is_something = self.is_something.clone();
+        self.promote_main_streets(&mut city);
         city
     }
-    fn generate_important_buildings(&mut self, n: usize, important_building_scale: i32) -> City {
+    /// Widen the busiest roads, if asked to
+    fn promote_main_streets(&mut self, city: &mut City) {
+        if let Some(share) = self.main_streets {
+            let now = Instant::now();
+            centrality::promote_main_streets(city, share);
+            self.is_something.clone_from(&city.is_something);
+            log::info!("Promoted main streets in {}", now.elapsed().as_secs_f32());
+        }
+    }
+    /// Generate the important buildings and the roads linking them, in a new city
+    pub fn generate_important_buildings(
+        &mut self,
+        n: usize,
+        important_building_scale: f64,
+    ) -> City {
         let mut city = City::new();
         // generate the important buildings with a smaller scale
 
@@ -93,211 +269,182 @@ impl CityGenerator {
             self.is_something.remove(&b1.door);
 
             // Keep track of the important building
-            city.important_buildings.push((b1.x, b1.y));
-            city.update_borders_from_new_building(&b1);
-            city.buildings.insert((b1.x, b1.y), b1);
+            city.insert_building(b1);
         }
-        let mut buildings = city.buildings.values().collect::<Vec<&Building>>(); // We'll iterate over the buildings
+        // We'll iterate over the buildings
+        let mut buildings = city
+            .buildings()
+            .values()
+            .cloned()
+            .collect::<Vec<Building>>();
         buildings.par_sort_by(|b1, b2| b1.x.cmp(&b2.x).then(b1.y.cmp(&b2.y)));
 
-        for &b1 in &buildings {
-            for &b2 in &buildings {
-                if b1 == b2 {
-                    continue;
-                }
-
-                let road = if let Some((road, _)) = self.generate_road(&city, b1, b2) {
-                    road
-                } else {
-                    vec![]
-                };
-                for (x, y) in &road {
-                    self.is_something.insert((*x, *y), CellType::Road);
-                }
-                city.roads.push(road);
+        let centers = buildings
+            .iter()
+            .map(|b| {
+                (
+                    f64::from(b.x) + f64::from(b.width) / 2.0,
+                    f64::from(b.y) + f64::from(b.height) / 2.0,
+                )
+            })
+            .collect::<Vec<(f64, f64)>>();
+        let pairs = self.important_network.edges(&centers);
+
+        // Roads go from door to door: a search stopping on the first road it meets would leave
+        // the buildings already on the network without a road of their own
+        let route = |generator: &Self, city: &City, (i, j): (usize, usize)| {
+            generator
+                .route_road(city, &buildings[i], &buildings[j], GoalPolicy::TargetDoor)
+                .map_or_else(Vec::new, |(road, _)| road)
+        };
+        if self.parallel_important_roads {
+            // Searches don't see each other's roads, so they can run in parallel, the roads are
+            // then registered in the order of the pairs to stay deterministic
+            let roads = pairs
+                .par_iter()
+                .map(|&pair| route(self, &city, pair))
+                .collect::<Vec<Vec<(i32, i32)>>>();
+            for road in roads {
+                self.add_important_road(&mut city, road);
             }
-        }
-        // Now, we will update everywthing to scale, so multiply everything by the scale factor
-        if important_building_scale > 1 {
-            self.is_something.clear();
-            city.min_x *= important_building_scale;
-            city.min_y *= important_building_scale;
-            city.max_x *= important_building_scale;
-            city.max_y *= important_building_scale;
-
-            for building in city.buildings.values_mut() {
-                building.x *= important_building_scale;
-                building.y *= important_building_scale;
-                building.width *= important_building_scale;
-                building.height *= important_building_scale;
-
-                building.door.0 *= important_building_scale;
-                building.door.1 *= important_building_scale;
-
-                for x in building.x..=building.x + building.width {
-                    for y in building.y..=building.y + building.height {
-                        self.is_something.insert((x, y), CellType::Building);
-                    }
-                }
-                self.is_something.remove(&building.door);
-            }
-            for road in &mut city.roads {
-                let mut scaled_road = vec![];
-                for i in 0..road.len() - 1 {
-                    let mut direction = (road[i + 1].0 - road[i].0, road[i + 1].1 - road[i].1);
-                    direction = (
-                        if direction.0 == 0 {
-                            0
-                        } else {
-                            direction.0 / direction.0.abs()
-                        },
-                        if direction.1 == 0 {
-                            0
-                        } else {
-                            direction.1 / direction.1.abs()
-                        },
-                    );
-
-                    let mut position = (
-                        road[i].0 * important_building_scale,
-                        road[i].1 * important_building_scale,
-                    );
-                    for _i in 0..important_building_scale {
-                        scaled_road.push(position);
-                        self.is_something.insert(position, CellType::Road);
-                        position = (position.0 + direction.0, position.1 + direction.1);
-                    }
-                }
-                *road = scaled_road;
+        } else {
+            // Each road may follow the ones routed before it
+            for &pair in &pairs {
+                let road = route(self, &city, pair);
+                self.add_important_road(&mut city, road);
             }
         }
+        // Now, we will update everything to scale
+        if (important_building_scale - 1.0).abs() > f64::EPSILON {
+            scaling::scale_city(&mut city, important_building_scale);
+            self.is_something.clone_from(&city.is_something);
+            self.reconnect_important_buildings(&mut city);
+        }
         city
     }
+    /// Route a road from every important building cut off from the others to the closest one
+    /// still reached
+    fn reconnect_important_buildings(&mut self, city: &mut City) {
+        let report = scaling::verify_connectivity(city);
+        if report.is_connected() {
+            return;
+        }
+        let cut_off = report
+            .unreachable_important_buildings
+            .iter()
+            .map(|key| city.buildings()[key].clone())
+            .collect::<Vec<_>>();
+        let reached = city
+            .important_buildings()
+            .iter()
+            .filter(|key| !report.unreachable_important_buildings.contains(key))
+            .map(|key| city.buildings()[key].clone())
+            .collect::<Vec<_>>();
+        for building in cut_off {
+            let closest = reached
+                .iter()
+                .min_by_key(|b| building.distance(b.door))
+                .expect("the first important building is always reached");
+            if let Some((road, _)) =
+                self.route_road(city, &building, closest, GoalPolicy::TargetDoor)
+            {
+                self.add_important_road(city, road);
+            }
+        }
+        city.rebuild_occupancy();
+        self.is_something.clone_from(&city.is_something);
+
+        let report = scaling::verify_connectivity(city);
+        if !report.is_connected() {
+            log::warn!(
+                "{} roads are broken and {} important buildings can't be reached",
+                report.broken_roads.len(),
+                report.unreachable_important_buildings.len()
+            );
+        }
+    }
+    /// Register a road between important buildings
+    fn add_important_road(&mut self, city: &mut City, road: Vec<(i32, i32)>) {
+        for (x, y) in &road {
+            self.is_something.insert((*x, *y), CellType::Road);
+        }
+        city.push_road(road, RoadTier::Arterial);
+    }
     /// Generate a random important building
+    #[allow(clippy::cast_possible_truncation)]
     fn generate_random_important_building(
         &mut self,
         city: &mut City,
-        scale_factor: i32,
+        scale_factor: f64,
     ) -> Building {
+        let max_distance =
+            (f64::from(self.important_buildings_max_distance) / (scale_factor * 2.0)) as i32;
         let (x, y) = (
-            self.rng.random_range(
-                -(self.important_buildings_max_distance / (scale_factor * 2))
-                    ..(self.important_buildings_max_distance / (scale_factor * 2)),
-            ),
-            self.rng.random_range(
-                (-self.important_buildings_max_distance / (scale_factor * 2))
-                    ..(self.important_buildings_max_distance / (scale_factor * 2)),
-            ),
+            self.rng.random_range(-max_distance..max_distance),
+            self.rng.random_range(-max_distance..max_distance),
         );
-        let width = (self.rng.random_range(self.width_bound.clone()) + scale_factor) / scale_factor;
+        let width =
+            (f64::from(self.rng.random_range(self.width_bound.clone())) / scale_factor) as i32 + 1;
         let height =
-            (self.rng.random_range(self.height_bound.clone()) + scale_factor) / scale_factor;
+            (f64::from(self.rng.random_range(self.height_bound.clone())) / scale_factor) as i32 + 1;
 
         let building =
             Building::with_random_door(&mut self.rng, x, y, width, height, 0).make_important();
-        if city.buildings.values().any(|b| b.overlaps(&building, 3)) {
+        if city.buildings().values().any(|b| b.overlaps(&building, 3)) {
             self.generate_random_important_building(city, scale_factor)
         } else {
             building
         }
     }
-    fn generate_buildings(&mut self, city: &mut City, mut n: usize) {
-        let init_n = n as f32;
+    /// Add `n` normal buildings to the city, each linked by a road to the closest important building
+    pub fn generate_buildings(&mut self, city: &mut City, mut n: usize) {
+        let total = n;
         while n > 0 {
+            let new_building = self.placement.propose(city, &mut self.rng, n, total);
             let Building {
-                door: _,
-                is_important: _,
-                x,
-                y,
+                x: spawn_x,
+                y: spawn_y,
                 width,
                 height,
-                id: _,
-            } = {
-                let values = city.buildings.values();
-                let mut a = values.into_iter().collect::<Vec<&Building>>();
-                a.par_sort_by(|b1, b2| b1.x.cmp(&b2.x).then(b1.y.cmp(&b2.y)));
-                a.into_iter().choose(&mut self.rng).unwrap()
-            };
-            let x_center = x + width / 2;
-            let y_center = y + height / 2;
-
-            // let distance_x = self.rng.random_range(self.distance_bound.clone());
-            // let distance_y = self.rng.random_range(self.distance_bound.clone());
-
-            let distance_x = ((self.distance_bound.end - self.distance_bound.start) as f32
-                * n as f32
-                / init_n) as i32
-                + self.distance_bound.start;
-
-            let distance_y = ((self.distance_bound.end - self.distance_bound.start) as f32
-                * (n as f32 / init_n)) as i32
-                + self.distance_bound.start;
-
-            let spawn_x = if self.rng.random_bool(0.5) {
-                x_center + distance_x
-            } else {
-                x_center - distance_x
-            };
-
-            let spawn_y = if self.rng.random_bool(0.5) {
-                y_center + distance_y
-            } else {
-                y_center - distance_y
-            };
-
-            let width = self.rng.random_range(self.width_bound.clone());
-            let height = self.rng.random_range(self.height_bound.clone());
+                ..
+            } = new_building;
 
             let offset = 8; // minimum distance between buildings
-            let new_building =
-                Building::with_random_door(&mut self.rng, spawn_x, spawn_y, width, height, n);
-            let overlaps =
-                        // seems inefficient but it's A* that's the bottleneck
-                            city
-                            .buildings
-                            .iter()
-                            .any(|(_, b)| b.overlaps(&new_building, offset) && b != &new_building)
-                            // it's okay to only check on building walls and not inside
-
-                            || (spawn_x..=spawn_x + width)
-                                .any(|x| self.is_something.contains_key(&(x, spawn_y)))
-
-                            || (spawn_x..=spawn_x + width
-            )                    .any(|x| self.is_something.contains_key(&(x, spawn_y + height)))
-
-                            || (spawn_y..=spawn_y + height)
-                                .any(|y| self.is_something.contains_key(&(spawn_x, y)))
-
-                            ||( spawn_y..=spawn_y + height
-            )                    .any(|y| self.is_something.contains_key(&(spawn_x + width, y)))
-
-                            ;
+            let overlaps = city
+                .buildings_near(spawn_x, spawn_y, width, height, offset)
+                .any(|b| b.overlaps(&new_building, offset) && b != &new_building)
+                // it's okay to only check on building walls and not inside
+                || (spawn_x..=spawn_x + width)
+                    .any(|x| self.is_something.contains_key(&(x, spawn_y)))
+                || (spawn_x..=spawn_x + width)
+                    .any(|x| self.is_something.contains_key(&(x, spawn_y + height)))
+                || (spawn_y..=spawn_y + height)
+                    .any(|y| self.is_something.contains_key(&(spawn_x, y)))
+                || (spawn_y..=spawn_y + height)
+                    .any(|y| self.is_something.contains_key(&(spawn_x + width, y)));
 
             if !overlaps {
-                let buildings_clone = city.buildings.clone();
-                let closest_important_building = buildings_clone
-                    .get(
-                        city.important_buildings
-                            .iter()
-                            .min_by_key(|(x, y)| (x - spawn_x).abs() + (y - spawn_y).abs())
-                            .unwrap(),
-                    )
-                    .unwrap();
+                let closest_important_building = city.buildings()[city
+                    .important_buildings()
+                    .iter()
+                    .min_by_key(|(x, y)| (x - spawn_x).abs() + (y - spawn_y).abs())
+                    .unwrap()]
+                .clone();
 
                 for x in spawn_x..=spawn_x + width {
                     for y in spawn_y..=spawn_y + height {
                         self.is_something.insert((x, y), CellType::Building);
                     }
                 }
-                self.is_something.remove(&(*x, *y));
+                self.is_something.remove(&new_building.door);
 
                 city.update_borders_from_new_building(&new_building);
                 let road = if let Some((road, _)) =
-                    self.generate_road(city, &new_building, closest_important_building)
+                    self.generate_road(city, &new_building, &closest_important_building)
                 {
                     road
                 } else {
-                    println!(
+                    log::warn!(
                         "No road found between {closest_important_building:?} and {spawn_x},{spawn_y}"
                     );
 
@@ -306,16 +453,17 @@ impl CityGenerator {
                 for (x, y) in &road {
                     self.is_something.insert((*x, *y), CellType::Road);
                 }
-                city.buildings.insert((spawn_x, spawn_y), new_building);
-                city.roads.push(road);
+                city.insert_building(new_building);
+                city.push_road(road, RoadTier::Local);
 
                 n -= 1;
             }
         }
     }
 
-    fn successors(&self, city: &City, p: (i32, i32)) -> Vec<((i32, i32), i32)> {
-        let (x, y) = p;
+    fn successors(&self, city: &City, node: RoadNode) -> Vec<(RoadNode, i32)> {
+        self.expanded_nodes.fetch_add(1, Ordering::Relaxed);
+        let (x, y) = node.pos;
 
         let mut successors = vec![];
         for i in -1..=1 {
@@ -336,47 +484,151 @@ impl CityGenerator {
                     continue;
                 }
 
-                let base_score = if i != 0 && j != 0 { 14 } else { 10 }; // if we go diagonally, the cost is sqrt(2)
-
-                match self.is_something.get(&(x + i, y + j)) {
-                    Some(CellType::Building) => match city.buildings.get(&(x + i, y + j)) {
-                        Some(building) => {
-                            // if we are in the door of the building, we can go through
-                            if building.door == (x + i, y + j) {
-                                successors.push(((x + i, y + j), base_score));
-                            }
-                        }
-                        None => continue,
+                let next = (x + i, y + j);
+                let kind = match self.is_something.get(&next) {
+                    Some(CellType::Building) => match city.buildings().get(&next) {
+                        // if we are in the door of the building, we can go through
+                        Some(building) if building.door == next => CellKind::Door,
+                        _ => CellKind::Wall,
                     },
-                    Some(CellType::Road) => successors.push(((x + i, y + j), base_score)),
-                    None => successors.push(((x + i, y + j), base_score * 5)), // penalize going through nothing
-                }
+                    Some(CellType::Road) => CellKind::Road,
+                    None => CellKind::Empty,
+                };
+                let Some(mut cost) = self.road_cost.step_cost(city, node.pos, next, kind) else {
+                    continue;
+                };
+
+                let heading = if self.road_cost.tracks_heading() {
+                    let heading = Heading::between(node.pos, next);
+                    if let (Some(from), Some(to)) = (node.heading, heading) {
+                        cost += self.road_cost.turn_cost(from, to);
+                    }
+                    heading
+                } else {
+                    None
+                };
+                successors.push((RoadNode { pos: next, heading }, cost));
             }
         }
 
         successors
     }
 
+    /// A* from the door of `start` to the goal of `goal_policy`, only going through the chunks of
+    /// the corridor if any
+    fn search_road(
+        &self,
+        city: &City,
+        (start, end): (&Building, &Building),
+        goal_policy: GoalPolicy,
+        corridor: Option<(&HashSet<(i32, i32)>, i32)>,
+    ) -> Option<(Vec<RoadNode>, i32)> {
+        let start = RoadNode {
+            pos: start.door,
+            heading: None,
+        };
+        astar(
+            &start,
+            |&node| {
+                let mut successors = self.successors(city, node);
+                if let Some((corridor, chunk_size)) = corridor {
+                    successors
+                        .retain(|(next, _)| corridor.contains(&chunk_of(next.pos, chunk_size)));
+                }
+                successors
+            },
+            |node| self.road_cost.heuristic(node.pos, end, goal_policy),
+            |node| match goal_policy {
+                GoalPolicy::AnyRoad => {
+                    matches!(self.is_something.get(&node.pos), Some(CellType::Road))
+                        || end.contains(node.pos)
+                }
+                GoalPolicy::TargetDoor => node.pos == end.door,
+            },
+        )
+    }
+
     /// Wrapper around A* to generate a road between two buildings
-    /// Road will start at the door of the first building and end as close as possible to the second
-    /// building or an existing road
-    #[allow(clippy::cast_possible_truncation)]
-    fn generate_road(
+    /// Road will start at the door of the first building and end, depending on the goal policy, on
+    /// the second building or an existing road, or on the door of the second building
+    pub fn generate_road(
         &self,
         city: &City,
         start: &Building,
         end: &Building,
     ) -> Option<(Vec<(i32, i32)>, i32)> {
-        let (x2, y2) = start.door;
-        astar(
-            &(x2, y2),
-            |&p| self.successors(city, p),
-            |&p| {
-                let (x, y) = p;
-                f64::from(((x - end.x + end.width).abs() + (y - end.y + end.height).abs()) * 10)
-                    .sqrt() as i32
-            },
-            |&p| matches!(self.is_something.get(&p), Some(CellType::Road)) || end.contains(p),
-        )
+        self.route_road(city, start, end, self.goal_policy)
+    }
+
+    /// Road from the door of `start` to the goal of `goal_policy`
+    fn route_road(
+        &self,
+        city: &City,
+        start: &Building,
+        end: &Building,
+        goal_policy: GoalPolicy,
+    ) -> Option<(Vec<(i32, i32)>, i32)> {
+        let buildings = (start, end);
+        let path = match self.router {
+            Router::AStar => self.search_road(city, buildings, goal_policy, None),
+            Router::Hierarchical { chunk_size } => self
+                .corridor(city, start, end, chunk_size)
+                .and_then(|corridor| {
+                    self.search_road(city, buildings, goal_policy, Some((&corridor, chunk_size)))
+                })
+                // The corridor may be too narrow to get around some buildings
+                .or_else(|| self.search_road(city, buildings, goal_policy, None)),
+        };
+        if path.is_none() {
+            self.failed_roads.fetch_add(1, Ordering::Relaxed);
+        }
+        path.map(|(path, cost)| {
+            let road = path.into_iter().map(|node| node.pos).collect::<Vec<_>>();
+            if self.straighten_roads {
+                let road = straighten_road(&road, |cell| {
+                    !matches!(self.is_something.get(&cell), Some(CellType::Building))
+                });
+                (road, cost)
+            } else {
+                (road, cost)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn important_buildings_are_all_connected() {
+        for network in [
+            ImportantNetwork::FullMesh,
+            ImportantNetwork::MinimumSpanningTree,
+        ] {
+            for seed in 0..3 {
+                let mut city_gen = CityGenerator::new(seed, 8..30, 8..30, 20..100, 500)
+                    .with_important_network(network);
+                let mut city = city_gen.generate_important_buildings(4, 1.0);
+                city.rebuild_occupancy();
+                let report = scaling::verify_connectivity(&city);
+                assert!(
+                    report.is_connected(),
+                    "{network:?}, seed {seed}: {report:?}"
+                );
+                assert!(city.roads().iter().all(|road| road.len() > 1));
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_important_buildings_stay_connected() {
+        for scale in [0.5, 2.5] {
+            let mut city_gen = CityGenerator::new(0, 8..30, 8..30, 20..100, 500);
+            let city = city_gen.generate_important_buildings(4, scale);
+            let report = scaling::verify_connectivity(&city);
+            assert!(report.is_connected(), "scale {scale}: {report:?}");
+            assert_eq!(city.buildings().len(), 4);
+        }
     }
 }
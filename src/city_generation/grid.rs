@@ -0,0 +1,360 @@
+use rand::Rng;
+
+use crate::{
+    building::Building,
+    city::{City, RoadTier},
+    city_generation::{CellType, CityGenerator},
+};
+
+/// Space left between two buildings of the same block
+const LOT_GAP: i32 = 2;
+
+/// Parameters of a planned (Hippodamian) grid city
+#[derive(Clone, Debug)]
+pub struct GridLayout {
+    /// Size of the square blocks between two streets
+    pub block_size: i32,
+    /// Width of the streets
+    pub street_width: i32,
+    /// Add two diagonal avenues crossing at the city center
+    pub diagonal_avenues: bool,
+}
+
+impl Default for GridLayout {
+    fn default() -> Self {
+        Self {
+            block_size: 40,
+            street_width: 2,
+            diagonal_avenues: false,
+        }
+    }
+}
+
+impl GridLayout {
+    /// Distance between two parallel streets
+    fn pitch(&self) -> i32 {
+        self.block_size + self.street_width
+    }
+
+    /// Top left interior cell of a block
+    fn block_origin(&self, block: (i32, i32)) -> (i32, i32) {
+        (
+            block.0 * self.pitch() + self.street_width,
+            block.1 * self.pitch() + self.street_width,
+        )
+    }
+
+    /// Check if a cell belongs to one of the diagonal avenues
+    fn is_avenue(&self, pos: (i32, i32)) -> bool {
+        if !self.diagonal_avenues {
+            return false;
+        }
+        let center = self.street_width + self.block_size / 2;
+        let (dx, dy) = (pos.0 - center, pos.1 - center);
+        let half_width = self.street_width.max(1);
+        (dx - dy).abs() <= half_width || (dx + dy).abs() <= half_width
+    }
+
+    /// Check if a rectangle (walls included) comes too close to an avenue
+    fn touches_avenue(&self, x: i32, y: i32, width: i32, height: i32) -> bool {
+        self.diagonal_avenues
+            && (x - 1..=x + width + 1)
+                .any(|cx| (y - 1..=y + height + 1).any(|cy| self.is_avenue((cx, cy))))
+    }
+}
+
+/// Blocks of a square of the given radius around the center block, closest first
+fn blocks_by_distance(radius: i32) -> Vec<(i32, i32)> {
+    let mut blocks = (-radius..=radius)
+        .flat_map(|bx| (-radius..=radius).map(move |by| (bx, by)))
+        .collect::<Vec<(i32, i32)>>();
+    blocks.sort_by_key(|&(bx, by)| (bx.abs().max(by.abs()), bx * bx + by * by, bx, by));
+    blocks
+}
+
+impl CityGenerator {
+    /// Generate a planned city: an orthogonal street grid whose central blocks are plazas holding
+    /// the important buildings, the other blocks being lined with buildings facing the streets.
+    pub(super) fn generate_grid(
+        &mut self,
+        layout: &GridLayout,
+        normal_buildings: usize,
+        important_buildings: usize,
+    ) -> City {
+        let layout = GridLayout {
+            block_size: layout.block_size.max(8),
+            street_width: layout.street_width.max(1),
+            ..layout.clone()
+        };
+        let mut city = City::new();
+
+        // Rough estimate of the number of buildings a block can hold, to size the grid
+        let average_width = (self.width_bound.start + self.width_bound.end) / 2 + LOT_GAP + 1;
+        let per_block = (2 * (layout.block_size / average_width)).max(1) as usize;
+        let needed_blocks = important_buildings + normal_buildings.div_ceil(per_block);
+        let mut radius = 0;
+        while ((2 * radius + 1) * (2 * radius + 1)) < needed_blocks as i32 {
+            radius += 1;
+        }
+
+        let mut remaining = normal_buildings;
+        let mut plazas = important_buildings;
+        let mut filled = 0;
+        loop {
+            let before = (remaining, plazas);
+            let blocks = blocks_by_distance(radius);
+            for &block in &blocks[filled..] {
+                if plazas > 0 {
+                    self.build_plaza(&mut city, &layout, block);
+                    plazas -= 1;
+                } else if remaining > 0 {
+                    self.fill_block(&mut city, &layout, block, &mut remaining);
+                }
+            }
+            filled = blocks.len();
+            if remaining == 0 && plazas == 0 {
+                break;
+            }
+            // Blocks too small for the narrowest lot stay empty, another ring wouldn't help
+            if (remaining, plazas) == before {
+                log::warn!(
+                    "Blocks of size {} can't hold buildings {} wide, {remaining} buildings not placed",
+                    layout.block_size,
+                    self.width_bound.start
+                );
+                break;
+            }
+            // Avenues ate some lots, grow the grid by one ring of blocks
+            radius += 1;
+        }
+
+        self.build_streets(&mut city, &layout, radius);
+        city
+    }
+
+    /// Place an important building in the middle of an empty block, linked to the closest street
+    fn build_plaza(&mut self, city: &mut City, layout: &GridLayout, block: (i32, i32)) {
+        let (x0, y0) = layout.block_origin(block);
+        let max_size = layout.block_size - 6;
+        let width = self
+            .rng
+            .random_range(self.width_bound.clone())
+            .clamp(2, max_size);
+        let height = self
+            .rng
+            .random_range(self.height_bound.clone())
+            .clamp(2, max_size);
+        let x = x0 + (layout.block_size - 1 - width) / 2;
+        let y = y0 + (layout.block_size - 1 - height) / 2;
+
+        let building =
+            Building::with_random_door(&mut self.rng, x, y, width, height, city.buildings().len())
+                .make_important();
+        let (door_x, door_y) = building.door;
+        let x1 = x0 + layout.block_size - 1;
+        let y1 = y0 + layout.block_size - 1;
+        // Walk from the door to the street surrounding the plaza
+        let approach = if door_y == y && door_x != x + width && door_x != x {
+            (y0..y).rev().map(|cy| (door_x, cy)).collect()
+        } else if door_y == y + height && door_x != x + width && door_x != x {
+            (y + height + 1..=y1).map(|cy| (door_x, cy)).collect()
+        } else if door_x == x {
+            (x0..x).rev().map(|cx| (cx, door_y)).collect()
+        } else {
+            (x + width + 1..=x1)
+                .map(|cx| (cx, door_y))
+                .collect::<Vec<(i32, i32)>>()
+        };
+
+        self.register_building(city, building);
+        for &cell in &approach {
+            self.is_something.insert(cell, CellType::Road);
+        }
+        city.push_road(approach, RoadTier::Local);
+    }
+
+    /// Line the northern and southern edges of a block with buildings whose doors face the street
+    fn fill_block(
+        &mut self,
+        city: &mut City,
+        layout: &GridLayout,
+        block: (i32, i32),
+        remaining: &mut usize,
+    ) {
+        let (x0, y0) = layout.block_origin(block);
+        let x1 = x0 + layout.block_size - 1;
+        let y1 = y0 + layout.block_size - 1;
+        let max_height = ((layout.block_size - LOT_GAP) / 2 - 1).max(1);
+        let min_width = (self.width_bound.start / 2).max(2);
+
+        for northern_row in [true, false] {
+            let mut cursor = x0;
+            while *remaining > 0 {
+                let width = self
+                    .rng
+                    .random_range(self.width_bound.clone())
+                    .min(x1 - cursor);
+                if width < min_width {
+                    break;
+                }
+                let height = self
+                    .rng
+                    .random_range(self.height_bound.clone())
+                    .min(max_height);
+                let y = if northern_row { y0 } else { y1 - height };
+                let x = cursor;
+                cursor += width + 1 + LOT_GAP;
+
+                if layout.touches_avenue(x, y, width, height) {
+                    continue;
+                }
+                let door = (
+                    self.rng.random_range(x + 1..x + width),
+                    if northern_row { y } else { y + height },
+                );
+                let building = Building {
+                    door,
+                    x,
+                    y,
+                    width,
+                    height,
+                    is_important: false,
+                    id: city.buildings().len(),
+                };
+                self.register_building(city, building);
+                *remaining -= 1;
+            }
+        }
+    }
+
+    /// Mark a building on the map and add it to the city
+    fn register_building(&mut self, city: &mut City, building: Building) {
+        for x in building.x..=building.x + building.width {
+            for y in building.y..=building.y + building.height {
+                self.is_something.insert((x, y), CellType::Building);
+            }
+        }
+        self.is_something.remove(&building.door);
+        city.insert_building(building);
+    }
+
+    /// Lay the streets, and the avenues if any, around all the blocks of the grid
+    fn build_streets(&mut self, city: &mut City, layout: &GridLayout, radius: i32) {
+        let start = -radius * layout.pitch();
+        let end = (radius + 1) * layout.pitch() + layout.street_width - 1;
+
+        for line in -radius..=radius + 1 {
+            for offset in 0..layout.street_width {
+                let position = line * layout.pitch() + offset;
+                let vertical = (start..=end).map(|y| (position, y)).collect::<Vec<_>>();
+                let horizontal = (start..=end).map(|x| (x, position)).collect::<Vec<_>>();
+                for road in [vertical, horizontal] {
+                    for &cell in &road {
+                        self.is_something.insert(cell, CellType::Road);
+                    }
+                    city.push_road(road, RoadTier::Street);
+                }
+            }
+        }
+
+        if !layout.diagonal_avenues {
+            return;
+        }
+        // Each lane of an avenue is a staircase, so every step is to a neighbor cell. The lanes
+        // are cut by the plazas' buildings, split them into continuous roads.
+        let center = layout.street_width + layout.block_size / 2;
+        let half_width = layout.street_width.max(1);
+        let mut avenues: Vec<Vec<(i32, i32)>> = vec![];
+        for slope in [1, -1] {
+            for offset in -half_width..=half_width {
+                let lane = (start..=end).flat_map(|x| {
+                    let y = center + slope * (x - center) + offset;
+                    [(x, y)].into_iter().chain((x < end).then_some((x + 1, y)))
+                });
+                let mut current = vec![];
+                for cell in lane {
+                    if let Some(CellType::Building) = self.is_something.get(&cell) {
+                        if !current.is_empty() {
+                            avenues.push(std::mem::take(&mut current));
+                        }
+                    } else {
+                        current.push(cell);
+                    }
+                }
+                if !current.is_empty() {
+                    avenues.push(current);
+                }
+            }
+        }
+        for road in avenues {
+            for &cell in &road {
+                self.is_something.insert(cell, CellType::Road);
+            }
+            city.push_road(road, RoadTier::Arterial);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{city_generation::LayoutStrategy, scaling::verify_connectivity};
+
+    fn grid_city(layout: GridLayout, normal: usize, important: usize) -> City {
+        CityGenerator::new(1, 8..30, 8..30, 20..100, 500)
+            .with_layout(LayoutStrategy::Grid(layout))
+            .generate(normal, important, 1.0)
+    }
+
+    #[test]
+    fn streets_and_avenues_are_continuous() {
+        let layout = GridLayout {
+            diagonal_avenues: true,
+            ..GridLayout::default()
+        };
+        let city = grid_city(layout, 60, 2);
+        let report = verify_connectivity(&city);
+        assert!(report.is_connected(), "{report:?}");
+        assert!(city
+            .roads_with_tiers()
+            .any(|(_, tier)| tier == RoadTier::Arterial));
+    }
+
+    #[test]
+    fn roads_go_around_the_buildings() {
+        let layout = GridLayout {
+            diagonal_avenues: true,
+            ..GridLayout::default()
+        };
+        let city = grid_city(layout, 60, 2);
+        assert_eq!(city.buildings().len(), 62);
+        for &(x, y) in city.roads().iter().flatten() {
+            assert!(
+                city.buildings_near(x, y, 0, 0, 0)
+                    .all(|b| !b.contains((x, y)) || b.door == (x, y)),
+                "road through a building at ({x}, {y})"
+            );
+        }
+    }
+
+    #[test]
+    fn blocks_too_small_for_any_building_are_left_empty() {
+        let layout = GridLayout {
+            block_size: 8,
+            ..GridLayout::default()
+        };
+        let city = CityGenerator::new(1, 20..30, 20..30, 20..100, 500)
+            .with_layout(LayoutStrategy::Grid(layout))
+            .generate(20, 0, 1.0);
+        assert!(city.buildings().is_empty());
+        assert!(!city.roads().is_empty());
+    }
+
+    #[test]
+    fn empty_cities_can_be_generated() {
+        let city = grid_city(GridLayout::default(), 0, 0);
+        assert!(city.buildings().is_empty());
+        let city = CityGenerator::new(1, 8..30, 8..30, 20..100, 500).generate(0, 0, 1.0);
+        assert!(city.buildings().is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::Ordering,
+};
+
+use pathfinding::prelude::astar;
+
+use crate::{
+    building::Building,
+    city::City,
+    city_generation::{CellType, CityGenerator},
+    road_cost::CellKind,
+};
+
+/// Smallest size of the chunks of the hierarchical router
+const MIN_CHUNK_SIZE: i32 = 2;
+
+/// Algorithm used to route roads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Router {
+    /// A* over every cell of the city
+    #[default]
+    AStar,
+    /// A* over square chunks of the city first, then over the cells of the chunks it went through
+    Hierarchical {
+        /// Size of the chunks, at least 2
+        chunk_size: i32,
+    },
+}
+
+impl Router {
+    /// Same router, with chunks large enough to be used
+    pub(super) fn validated(self) -> Self {
+        match self {
+            Self::Hierarchical { chunk_size } if chunk_size < MIN_CHUNK_SIZE => {
+                log::warn!("Chunks of size {chunk_size} are too small, using {MIN_CHUNK_SIZE}");
+                Self::Hierarchical {
+                    chunk_size: MIN_CHUNK_SIZE,
+                }
+            }
+            router => router,
+        }
+    }
+}
+
+/// Chunk containing a cell
+pub(super) fn chunk_of(pos: (i32, i32), chunk_size: i32) -> (i32, i32) {
+    (pos.0.div_euclid(chunk_size), pos.1.div_euclid(chunk_size))
+}
+
+impl CityGenerator {
+    /// Estimated cost of crossing a chunk, `None` if nothing can be walked on in it
+    fn chunk_cost(&self, city: &City, chunk: (i32, i32), chunk_size: i32) -> Option<i32> {
+        let (x0, y0) = (chunk.0 * chunk_size, chunk.1 * chunk_size);
+        // A sparse sample of the cells is enough to tell roads, buildings and empty land apart
+        let stride = (chunk_size / 4).max(1) as usize;
+        let (mut cells, mut roads, mut walls) = (0, 0, 0);
+        for x in (x0..x0 + chunk_size).step_by(stride) {
+            for y in (y0..y0 + chunk_size).step_by(stride) {
+                cells += 1;
+                match self.is_something.get(&(x, y)) {
+                    Some(CellType::Road) => roads += 1,
+                    Some(CellType::Building) => walls += 1,
+                    None => {}
+                }
+            }
+        }
+        let walkable = cells - walls;
+        if walkable == 0 {
+            return None;
+        }
+        let empties = walkable - roads;
+
+        let center = (x0 + chunk_size / 2, y0 + chunk_size / 2);
+        let road_step = self
+            .road_cost
+            .step_cost(city, center, center, CellKind::Road)
+            .unwrap_or(0);
+        let empty_step = self
+            .road_cost
+            .step_cost(city, center, center, CellKind::Empty)
+            .unwrap_or(0);
+        let average_step = (roads * road_step + empties * empty_step) / walkable;
+        // Chunks full of buildings are harder to cross than their free cells let think
+        Some(chunk_size * average_step * cells / walkable)
+    }
+
+    /// Chunks a road from `start` to `end` should stay in: the chunks of the cheapest path on the
+    /// coarse grid and their neighbors
+    pub(super) fn corridor(
+        &self,
+        city: &City,
+        start: &Building,
+        end: &Building,
+        chunk_size: i32,
+    ) -> Option<HashSet<(i32, i32)>> {
+        let (min_chunk, max_chunk) = (
+            chunk_of((city.min_x, city.min_y), chunk_size),
+            chunk_of((city.max_x, city.max_y), chunk_size),
+        );
+        let goal = chunk_of(end.door, chunk_size);
+        // The coarse path only has to be good enough to guide the fine search, so estimate the
+        // remaining cost as if it was all empty land, which keeps this search narrow
+        let start_chunk = chunk_of(start.door, chunk_size);
+        let empty_chunk_cost = chunk_size
+            * self
+                .road_cost
+                .step_cost(city, start.door, start.door, CellKind::Empty)
+                .unwrap_or_else(|| self.road_cost.min_step_cost());
+        let mut costs = HashMap::new();
+
+        let (chunks, _) = astar(
+            &start_chunk,
+            |&(cx, cy)| {
+                self.expanded_nodes.fetch_add(1, Ordering::Relaxed);
+                [(cx + 1, cy), (cx - 1, cy), (cx, cy + 1), (cx, cy - 1)]
+                    .into_iter()
+                    .filter(|&(nx, ny)| {
+                        nx >= min_chunk.0
+                            && nx <= max_chunk.0
+                            && ny >= min_chunk.1
+                            && ny <= max_chunk.1
+                    })
+                    .filter_map(|chunk| {
+                        let cost = *costs
+                            .entry(chunk)
+                            .or_insert_with(|| self.chunk_cost(city, chunk, chunk_size));
+                        cost.map(|cost| (chunk, cost))
+                    })
+                    .collect::<Vec<_>>()
+            },
+            |&(cx, cy)| ((cx - goal.0).abs() + (cy - goal.1).abs()) * empty_chunk_cost,
+            |&chunk| chunk == goal,
+        )?;
+
+        Some(
+            chunks
+                .into_iter()
+                .flat_map(|(cx, cy)| {
+                    (-1..=1).flat_map(move |i| (-1..=1).map(move |j| (cx + i, cy + j)))
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::road_cost::GoalPolicy;
+
+    fn building(x: i32, y: i32, door: (i32, i32)) -> Building {
+        Building {
+            door,
+            x,
+            y,
+            width: 6,
+            height: 6,
+            is_important: true,
+            id: 0,
+        }
+    }
+
+    fn generator(chunk_size: i32) -> CityGenerator {
+        CityGenerator::new(0, 8..30, 8..30, 20..100, 500)
+            .with_goal_policy(GoalPolicy::TargetDoor)
+            .with_router(Router::Hierarchical { chunk_size })
+    }
+
+    #[test]
+    fn chunks_are_at_least_two_cells_wide() {
+        assert_eq!(
+            Router::Hierarchical { chunk_size: 0 }.validated(),
+            Router::Hierarchical {
+                chunk_size: MIN_CHUNK_SIZE
+            }
+        );
+        assert_eq!(Router::AStar.validated(), Router::AStar);
+        assert_eq!(chunk_of((-1, 8), 8), (-1, 1));
+        assert_eq!(chunk_of((-9, 7), 8), (-2, 0));
+    }
+
+    #[test]
+    fn chunks_of_buildings_cost_more_than_chunks_of_roads() {
+        let mut city_gen = generator(8);
+        let city = City::new();
+        for x in 0..8 {
+            for y in 0..8 {
+                city_gen.is_something.insert((x, y), CellType::Building);
+                city_gen.is_something.insert((x + 8, y), CellType::Road);
+            }
+        }
+        assert_eq!(city_gen.chunk_cost(&city, (0, 0), 8), None);
+        let road = city_gen.chunk_cost(&city, (1, 0), 8).unwrap();
+        let empty = city_gen.chunk_cost(&city, (2, 0), 8).unwrap();
+        assert!(road < empty, "{road} >= {empty}");
+    }
+
+    #[test]
+    fn roads_stay_in_their_corridor() {
+        let (start, end) = (building(0, 0, (3, 6)), building(80, 40, (83, 40)));
+        let mut city = City::new();
+        city.insert_building(start.clone());
+        city.insert_building(end.clone());
+        city.update_borders();
+        let city_gen = generator(8);
+
+        let corridor = city_gen.corridor(&city, &start, &end, 8).unwrap();
+        assert!(corridor.contains(&chunk_of(start.door, 8)));
+        assert!(corridor.contains(&chunk_of(end.door, 8)));
+        let coarse_nodes = city_gen.expanded_nodes();
+        assert!(coarse_nodes > 0);
+
+        let (road, _) = city_gen.generate_road(&city, &start, &end).unwrap();
+        assert_eq!(road.first(), Some(&start.door));
+        assert_eq!(road.last(), Some(&end.door));
+        assert!(road
+            .iter()
+            .all(|&cell| corridor.contains(&chunk_of(cell, 8))));
+        // The coarse search of the road is counted with the fine one
+        assert!(city_gen.expanded_nodes() > 2 * coarse_nodes);
+    }
+
+    #[test]
+    fn hierarchical_routing_connects_the_important_buildings() {
+        let mut city_gen = generator(16);
+        let mut city = city_gen.generate_important_buildings(4, 1.0);
+        city.rebuild_occupancy();
+        let report = crate::scaling::verify_connectivity(&city);
+        assert!(report.is_connected(), "{report:?}");
+        assert_eq!(city_gen.failed_roads(), 0);
+    }
+}
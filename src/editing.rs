@@ -0,0 +1,538 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    building::Building,
+    city::{City, RoadTier},
+    city_generation::CellType,
+    export::SavedCity,
+};
+
+/// Smallest width and height of a building placed by hand, so it has walls between its corners
+const MIN_SIZE: i32 = 2;
+/// Number of edits that can be undone
+const HISTORY_LENGTH: usize = 100;
+
+/// Change made by hand to a city
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Edit {
+    /// Add a building covering `x..=x + width` and `y..=y + height`
+    PlaceBuilding {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    /// Move the building with the given key, door included
+    MoveBuilding { key: (i32, i32), by: (i32, i32) },
+    /// Change the size of the building with the given key, keeping its top left corner
+    ResizeBuilding {
+        key: (i32, i32),
+        width: i32,
+        height: i32,
+    },
+    /// Remove the building with the given key
+    RemoveBuilding { key: (i32, i32) },
+    /// Add a local road along the cells, cut where it would go through a building
+    DrawRoad(Vec<(i32, i32)>),
+    /// Remove the cells from the roads, splitting the roads going through them
+    EraseRoad(Vec<(i32, i32)>),
+}
+
+/// Check if a rectangle can hold a building: no road, door or other building than `ignored` on it
+fn is_free(
+    city: &City,
+    (x, y, width, height): (i32, i32, i32, i32),
+    ignored: Option<(i32, i32)>,
+) -> bool {
+    let is_ignored = |cell| ignored.is_some_and(|key| city.buildings()[&key].contains(cell));
+    let cells_free = (x..=x + width).all(|cx| {
+        (y..=y + height)
+            .all(|cy| !city.is_something.contains_key(&(cx, cy)) || is_ignored((cx, cy)))
+    });
+    cells_free
+        && city
+            .buildings_near(x, y, width, height, 0)
+            .filter(|b| Some((b.x, b.y)) != ignored)
+            .all(|b| !(x..=x + width).contains(&b.door.0) || !(y..=y + height).contains(&b.door.1))
+}
+
+/// Door of a building, on a wall next to a road if there is one, else in the middle of its
+/// southern wall
+fn door_for(city: &City, (x, y, width, height): (i32, i32, i32, i32)) -> (i32, i32) {
+    let walls = (x + 1..x + width)
+        .flat_map(|cx| {
+            [
+                ((cx, y), (cx, y - 1)),
+                ((cx, y + height), (cx, y + height + 1)),
+            ]
+        })
+        .chain((y + 1..y + height).flat_map(|cy| {
+            [
+                ((x, cy), (x - 1, cy)),
+                ((x + width, cy), (x + width + 1, cy)),
+            ]
+        }));
+    walls
+        .filter(|(_, outside)| matches!(city.is_something.get(outside), Some(CellType::Road)))
+        .map(|(wall, _)| wall)
+        .min()
+        .unwrap_or((x + width / 2, y + height))
+}
+
+/// Check if a cell is on a wall of a building, corners excluded
+fn is_on_wall(building: &Building, (cx, cy): (i32, i32)) -> bool {
+    let (x, y, width, height) = (building.x, building.y, building.width, building.height);
+    let horizontal = (cy == y || cy == y + height) && (x < cx && cx < x + width);
+    let vertical = (cx == x || cx == x + width) && (y < cy && cy < y + height);
+    horizontal || vertical
+}
+
+/// Put a building in the place of the one with the given key, keeping its order. The roads to its
+/// old door are trimmed if the door moved. The borders only grow, so they still hold the roads.
+fn replace_building(city: &mut City, key: (i32, i32), building: Building) {
+    city.update_borders_from_new_building(&building);
+    let door = building.door;
+    if let Some(old) = city.replace_building(key, building) {
+        if old.door != door {
+            trim_roads_to(city, old.door);
+        }
+    }
+    city.rebuild_occupancy();
+}
+
+/// Cut the roads ending at a door that was removed, from the door to the first cell still linked
+/// to another road or door. The roads linked to nothing else are removed.
+fn trim_roads_to(city: &mut City, door: (i32, i32)) {
+    let (dangling, kept): (Vec<_>, Vec<_>) = city
+        .take_roads()
+        .into_iter()
+        .partition(|(road, _)| road.first() == Some(&door) || road.last() == Some(&door));
+    let on_roads = kept
+        .iter()
+        .flat_map(|(road, _)| road.iter().copied())
+        .collect::<HashSet<_>>();
+    // Ends of roads and doors are also linked to the road cells next to them
+    let ends = kept
+        .iter()
+        .flat_map(|(road, _)| road.first().into_iter().chain(road.last()).copied())
+        .chain(city.buildings().values().map(|b| b.door))
+        .collect::<HashSet<_>>();
+    let is_linked = |(x, y): (i32, i32)| {
+        on_roads.contains(&(x, y))
+            || [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+                .iter()
+                .any(|cell| ends.contains(cell))
+    };
+    for (road, tier) in kept {
+        city.push_road(road, tier);
+    }
+    for (mut road, tier) in dangling {
+        if road.first() != Some(&door) {
+            road.reverse();
+        }
+        // What is left of a road must still be a step long
+        match road
+            .iter()
+            .position(|&cell| cell != door && is_linked(cell))
+        {
+            Some(start) if road.len() - start >= 2 => city.push_road(road.split_off(start), tier),
+            _ => {}
+        }
+    }
+}
+
+impl Edit {
+    /// Check if the edit can be made: its building exists and fits where it goes, or some of its
+    /// cells can be drawn on or erased
+    pub fn is_possible(&self, city: &City) -> bool {
+        let is_building = |cell| matches!(city.is_something.get(cell), Some(CellType::Building));
+        match self {
+            &Self::PlaceBuilding {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                width >= MIN_SIZE
+                    && height >= MIN_SIZE
+                    && is_free(city, (x, y, width, height), None)
+            }
+            &Self::MoveBuilding { key, by } => {
+                by != (0, 0)
+                    && city.buildings().get(&key).is_some_and(|b| {
+                        is_free(city, (b.x + by.0, b.y + by.1, b.width, b.height), Some(key))
+                    })
+            }
+            &Self::ResizeBuilding { key, width, height } => {
+                width >= MIN_SIZE
+                    && height >= MIN_SIZE
+                    && city.buildings().get(&key).is_some_and(|b| {
+                        (width, height) != (b.width, b.height)
+                            && is_free(city, (b.x, b.y, width, height), Some(key))
+                    })
+            }
+            Self::RemoveBuilding { key } => city.buildings().contains_key(key),
+            Self::DrawRoad(cells) => cells.iter().any(|cell| !is_building(cell)),
+            Self::EraseRoad(cells) => cells
+                .iter()
+                .any(|cell| matches!(city.is_something.get(cell), Some(CellType::Road))),
+        }
+    }
+
+    /// Key a building has after the edit, if it had `key` before
+    pub fn key_after(&self, key: (i32, i32)) -> (i32, i32) {
+        match *self {
+            Self::MoveBuilding { key: moved, by } if moved == key => (key.0 + by.0, key.1 + by.1),
+            _ => key,
+        }
+    }
+
+    /// Change the city, keeping its index, borders and occupancy map up to date. The borders
+    /// never shrink, so they keep holding every road. Returns whether the edit was possible, the
+    /// city being left untouched otherwise.
+    pub fn apply(self, city: &mut City) -> bool {
+        if !self.is_possible(city) {
+            return false;
+        }
+        match self {
+            Self::PlaceBuilding {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let id = city
+                    .buildings()
+                    .values()
+                    .map(|b| b.id + 1)
+                    .max()
+                    .unwrap_or(0);
+                city.insert_building(Building {
+                    door: door_for(city, (x, y, width, height)),
+                    x,
+                    y,
+                    width,
+                    height,
+                    is_important: false,
+                    id,
+                });
+                city.rebuild_occupancy();
+            }
+            Self::MoveBuilding { key, by } => {
+                let building = &city.buildings()[&key];
+                let moved = Building {
+                    x: building.x + by.0,
+                    y: building.y + by.1,
+                    door: (building.door.0 + by.0, building.door.1 + by.1),
+                    ..building.clone()
+                };
+                replace_building(city, key, moved);
+            }
+            Self::ResizeBuilding { key, width, height } => {
+                let building = &city.buildings()[&key];
+                let mut resized = Building {
+                    width,
+                    height,
+                    ..building.clone()
+                };
+                if !is_on_wall(&resized, resized.door) {
+                    resized.door = door_for(city, (resized.x, resized.y, width, height));
+                }
+                replace_building(city, key, resized);
+            }
+            Self::RemoveBuilding { key } => {
+                if let Some(removed) = city.remove_building(key) {
+                    trim_roads_to(city, removed.door);
+                }
+                city.rebuild_occupancy();
+            }
+            Self::DrawRoad(cells) => {
+                let mut roads = vec![];
+                let mut road: Vec<(i32, i32)> = vec![];
+                for cell in cells {
+                    if road.last() == Some(&cell) {
+                        continue;
+                    }
+                    if matches!(city.is_something.get(&cell), Some(CellType::Building)) {
+                        if !road.is_empty() {
+                            roads.push(std::mem::take(&mut road));
+                        }
+                    } else {
+                        road.push(cell);
+                    }
+                }
+                if !road.is_empty() {
+                    roads.push(road);
+                }
+                for road in roads {
+                    city.update_borders_from_new_road(&road);
+                    city.push_road(road, RoadTier::Local);
+                }
+                city.rebuild_occupancy();
+            }
+            Self::EraseRoad(cells) => {
+                let cells = cells.into_iter().collect::<HashSet<_>>();
+                for (road, tier) in city.take_roads() {
+                    for piece in road.split(|cell| cells.contains(cell)) {
+                        if !piece.is_empty() {
+                            city.push_road(piece.to_vec(), tier);
+                        }
+                    }
+                }
+                city.rebuild_occupancy();
+            }
+        }
+        true
+    }
+}
+
+/// City before or after an edit. Its borders are kept, as they may hold roads the buildings
+/// alone wouldn't give.
+struct Snapshot {
+    city: SavedCity,
+    borders: (i32, i32, i32, i32),
+}
+
+impl Snapshot {
+    fn new(city: &City) -> Self {
+        Self {
+            city: SavedCity::new(city),
+            borders: (city.min_x, city.min_y, city.max_x, city.max_y),
+        }
+    }
+
+    fn into_city(self) -> City {
+        let mut city = self.city.into_city();
+        (city.min_x, city.min_y, city.max_x, city.max_y) = self.borders;
+        city
+    }
+}
+
+/// Edits that can be undone and redone, as the cities before them. The oldest edits are
+/// forgotten past `HISTORY_LENGTH`.
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl History {
+    /// Apply an edit, remembering the city before it. Returns whether the city changed.
+    pub fn apply(&mut self, city: &mut City, edit: Edit) -> bool {
+        // Checked first, so rejected edits don't copy the city
+        if !edit.is_possible(city) {
+            return false;
+        }
+        let before = Snapshot::new(city);
+        edit.apply(city);
+        self.undo.push_back(before);
+        if self.undo.len() > HISTORY_LENGTH {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+        true
+    }
+
+    /// Go back to the city before the last edit. Returns whether there was one.
+    pub fn undo(&mut self, city: &mut City) -> bool {
+        let Some(previous) = self.undo.pop_back() else {
+            return false;
+        };
+        self.redo.push(Snapshot::new(city));
+        *city = previous.into_city();
+        true
+    }
+
+    /// Apply again the last edit undone. Returns whether there was one.
+    pub fn redo(&mut self, city: &mut City) -> bool {
+        let Some(next) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push_back(Snapshot::new(city));
+        *city = next.into_city();
+        true
+    }
+
+    /// Forget every edit, for another city
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::to_json;
+
+    /// A building with its door on its southern wall, and an access road from the door to a
+    /// street running below it
+    fn city() -> City {
+        let mut city = City::new();
+        city.insert_building(Building {
+            door: (2, 4),
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 4,
+            is_important: false,
+            id: 0,
+        });
+        city.push_road((-5..=15).map(|x| (x, 7)).collect(), RoadTier::Street);
+        city.push_road(vec![(2, 4), (2, 5), (2, 6)], RoadTier::Local);
+        city.update_borders();
+        city.rebuild_occupancy();
+        city
+    }
+
+    /// What an edit and its undoing should change
+    fn state(city: &City) -> (String, (i32, i32, i32, i32), usize) {
+        (
+            to_json(city),
+            (city.min_x, city.min_y, city.max_x, city.max_y),
+            city.is_something.len(),
+        )
+    }
+
+    #[test]
+    fn every_edit_can_be_undone_and_redone() {
+        let edits = [
+            Edit::PlaceBuilding {
+                x: 10,
+                y: 0,
+                width: 3,
+                height: 3,
+            },
+            Edit::MoveBuilding {
+                key: (0, 0),
+                by: (0, -40),
+            },
+            Edit::ResizeBuilding {
+                key: (0, 0),
+                width: 3,
+                height: 3,
+            },
+            Edit::RemoveBuilding { key: (0, 0) },
+            Edit::DrawRoad(vec![(60, 0), (60, 1)]),
+            Edit::EraseRoad(vec![(0, 7)]),
+        ];
+        for edit in edits {
+            let mut city = city();
+            let mut history = History::default();
+            let before = state(&city);
+            assert!(history.apply(&mut city, edit.clone()), "{edit:?}");
+            let after = state(&city);
+            assert_ne!(after, before, "{edit:?}");
+
+            assert!(history.undo(&mut city));
+            assert_eq!(state(&city), before, "{edit:?}");
+            assert!(!history.undo(&mut city));
+            assert!(history.redo(&mut city));
+            assert_eq!(state(&city), after, "{edit:?}");
+            assert!(!history.redo(&mut city));
+        }
+    }
+
+    #[test]
+    fn impossible_edits_leave_the_city_untouched() {
+        let edits = [
+            // Too small
+            Edit::PlaceBuilding {
+                x: 10,
+                y: 0,
+                width: MIN_SIZE - 1,
+                height: 3,
+            },
+            Edit::ResizeBuilding {
+                key: (0, 0),
+                width: 3,
+                height: MIN_SIZE - 1,
+            },
+            // Over a building or a road
+            Edit::PlaceBuilding {
+                x: 1,
+                y: 1,
+                width: 3,
+                height: 3,
+            },
+            Edit::PlaceBuilding {
+                x: 8,
+                y: 5,
+                width: 3,
+                height: 3,
+            },
+            Edit::MoveBuilding {
+                key: (0, 0),
+                by: (0, 4),
+            },
+            // No such building
+            Edit::MoveBuilding {
+                key: (1, 1),
+                by: (1, 0),
+            },
+            Edit::ResizeBuilding {
+                key: (1, 1),
+                width: 3,
+                height: 3,
+            },
+            Edit::RemoveBuilding { key: (1, 1) },
+            // No road to erase, or only cells of a building to draw one on
+            Edit::EraseRoad(vec![(30, 30)]),
+            Edit::DrawRoad(vec![(1, 1), (1, 2)]),
+        ];
+        for edit in edits {
+            let mut city = city();
+            let mut history = History::default();
+            let before = state(&city);
+            assert!(!history.apply(&mut city, edit.clone()), "{edit:?}");
+            assert_eq!(state(&city), before, "{edit:?}");
+            assert!(!history.undo(&mut city));
+        }
+    }
+
+    #[test]
+    fn removing_a_building_trims_the_roads_to_its_door() {
+        let mut city = city();
+        // Crosses the street and goes on below it
+        city.push_road((4..=10).map(|y| (2, y)).collect(), RoadTier::Local);
+        let borders = (city.min_x, city.min_y, city.max_x, city.max_y);
+        assert!(Edit::RemoveBuilding { key: (0, 0) }.apply(&mut city));
+
+        // The access road only ended next to the street, so nothing uses it any more
+        let roads = city.roads_with_tiers().collect::<Vec<_>>();
+        assert_eq!(roads.len(), 2);
+        assert_eq!(roads[0].1, RoadTier::Street);
+        assert_eq!(roads[1].0, &(7..=10).map(|y| (2, y)).collect::<Vec<_>>());
+        assert_eq!((city.min_x, city.min_y, city.max_x, city.max_y), borders);
+    }
+
+    #[test]
+    fn moving_a_building_trims_the_roads_to_its_old_door() {
+        let mut city = city();
+        // Ends on the street, which would leave a single cell
+        city.push_road(vec![(2, 4), (2, 5), (2, 6), (2, 7)], RoadTier::Local);
+        let edit = Edit::MoveBuilding {
+            key: (0, 0),
+            by: (20, -20),
+        };
+        assert_eq!(edit.key_after((0, 0)), (20, -20));
+        assert_eq!(edit.key_after((1, 1)), (1, 1));
+        assert!(edit.apply(&mut city));
+
+        assert_eq!(city.roads().len(), 1);
+        assert_eq!(city.road_tiers(), [RoadTier::Street]);
+        assert_eq!(city.buildings()[&(20, -20)].door, (22, -16));
+    }
+
+    #[test]
+    fn oldest_edits_are_forgotten() {
+        let mut city = city();
+        let mut history = History::default();
+        for i in 0..=HISTORY_LENGTH as i32 {
+            assert!(history.apply(&mut city, Edit::DrawRoad(vec![(100 + i, 100)])));
+        }
+        let undone = std::iter::from_fn(|| history.undo(&mut city).then_some(())).count();
+        assert_eq!(undone, HISTORY_LENGTH);
+        assert_eq!(city.roads().len(), 3);
+    }
+}
@@ -0,0 +1,288 @@
+use std::{fmt::Write as _, fs, io, path::Path};
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    accessibility::AccessibilityMap,
+    building::Building,
+    city::{City, RoadTier},
+    city_generation::CellType,
+    road_network::RoadNetwork,
+};
+
+/// Format a city can be written to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One pixel per cell
+    Png,
+    /// Buildings as rectangles and roads as polylines
+    Svg,
+    /// Buildings and roads, the format cities are saved in and loaded from
+    Json,
+    /// Buildings as polygons and roads as line strings, in cell coordinates
+    GeoJson,
+    /// Distance by road to the closest important building, one pixel per cell
+    Heatmap,
+}
+
+/// What is saved of a city, everything else is rebuilt on load
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedCity {
+    /// Buildings, in insertion order
+    buildings: Vec<Building>,
+    roads: Vec<Vec<(i32, i32)>>,
+    /// Tier of each road, cities saved before tiers existed only have local roads
+    #[serde(default)]
+    road_tiers: Vec<RoadTier>,
+}
+
+impl SavedCity {
+    pub fn new(city: &City) -> Self {
+        Self {
+            buildings: city
+                .building_order()
+                .iter()
+                .map(|key| city.buildings()[key].clone())
+                .collect(),
+            roads: city.roads().to_vec(),
+            road_tiers: city.road_tiers().to_vec(),
+        }
+    }
+
+    /// Rebuild the city, with its index, borders and occupancy map
+    pub fn into_city(self) -> City {
+        let mut city = City::new();
+        for building in self.buildings {
+            city.insert_building(building);
+        }
+        let tiers = self
+            .road_tiers
+            .into_iter()
+            .chain(std::iter::repeat(RoadTier::Local));
+        for (road, tier) in self.roads.into_iter().zip(tiers) {
+            city.push_road(road, tier);
+        }
+        if !city.buildings().is_empty() {
+            city.update_borders();
+        }
+        city.rebuild_occupancy();
+        city
+    }
+}
+
+fn invalid_data(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Write a city to `path`
+pub fn export_city(city: &City, format: ExportFormat, path: &Path) -> io::Result<()> {
+    match format {
+        ExportFormat::Png => to_png(city).save(path).map_err(invalid_data),
+        ExportFormat::Svg => fs::write(path, to_svg(city)),
+        ExportFormat::Json => fs::write(path, to_json(city)),
+        ExportFormat::GeoJson => fs::write(path, to_geojson(city).to_string()),
+        ExportFormat::Heatmap => {
+            AccessibilityMap::from_important_buildings(city, &RoadNetwork::new(city))
+                .to_png(city)
+                .save(path)
+                .map_err(invalid_data)
+        }
+    }
+}
+
+/// Save a city so it can be loaded back with [`load_city`]
+pub fn save_city(city: &City, path: &Path) -> io::Result<()> {
+    export_city(city, ExportFormat::Json, path)
+}
+
+/// Load a city saved with [`save_city`], rebuilding its index, borders and occupancy map
+pub fn load_city(path: &Path) -> io::Result<City> {
+    let saved: SavedCity =
+        serde_json::from_str(&fs::read_to_string(path)?).map_err(invalid_data)?;
+    Ok(saved.into_city())
+}
+
+/// Serialize the buildings and roads of a city
+pub fn to_json(city: &City) -> String {
+    serde_json::to_string(&SavedCity::new(city)).expect("a city can always be serialized")
+}
+
+/// Render a city with one pixel per cell, inside its borders
+#[allow(clippy::cast_sign_loss)]
+pub fn to_png(city: &City) -> RgbaImage {
+    let (width, height) = (
+        (city.max_x - city.min_x).max(0) as u32,
+        (city.max_y - city.min_y).max(0) as u32,
+    );
+    RgbaImage::from_fn(width, height, |x, y| {
+        #[allow(clippy::cast_possible_wrap)]
+        let cell = (city.min_x + x as i32, city.min_y + y as i32);
+        match city.is_something.get(&cell) {
+            Some(CellType::Building) => Rgba([255, 0, 0, 255]),
+            Some(CellType::Road) => Rgba([0, 255, 0, 255]),
+            None => Rgba([0, 0, 0, 255]),
+        }
+    })
+}
+
+/// Render a city as a vector image, one unit per cell
+pub fn to_svg(city: &City) -> String {
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        city.min_x,
+        city.min_y,
+        city.max_x - city.min_x,
+        city.max_y - city.min_y
+    );
+    svg.push_str(r#"<rect x="-100%" y="-100%" width="300%" height="300%" fill="black"/>"#);
+    // Writing to a String can't fail, so the results are ignored
+    for road in city.roads() {
+        let points = road
+            .iter()
+            .map(|&(x, y)| format!("{},{}", f64::from(x) + 0.5, f64::from(y) + 0.5))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let _ = write!(
+            svg,
+            r#"<polyline points="{points}" fill="none" stroke="lime" stroke-width="1"/>"#
+        );
+    }
+    for key in city.building_order() {
+        let building = &city.buildings()[key];
+        let fill = if building.is_important {
+            "orange"
+        } else {
+            "red"
+        };
+        let _ = write!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{fill}"/><rect x="{}" y="{}" width="1" height="1" fill="black"/>"#,
+            building.x,
+            building.y,
+            building.width + 1,
+            building.height + 1,
+            building.door.0,
+            building.door.1,
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Describe a city as a `GeoJSON` feature collection, in cell coordinates
+pub fn to_geojson(city: &City) -> Value {
+    let buildings = city.building_order().iter().map(|key| {
+        let b = &city.buildings()[key];
+        let (x0, y0, x1, y1) = (b.x, b.y, b.x + b.width + 1, b.y + b.height + 1);
+        json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [[[x0, y0], [x1, y0], [x1, y1], [x0, y1], [x0, y0]]],
+            },
+            "properties": {
+                "kind": "building",
+                "id": b.id,
+                "important": b.is_important,
+                "door": [b.door.0, b.door.1],
+            },
+        })
+    });
+    let roads = city
+        .roads_with_tiers()
+        .enumerate()
+        .filter(|(_, (road, _))| road.len() > 1)
+        .map(|(i, (road, tier))| {
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": road.iter().map(|&(x, y)| [x, y]).collect::<Vec<_>>(),
+                },
+                "properties": { "kind": "road", "id": i, "tier": tier },
+            })
+        });
+    json!({
+        "type": "FeatureCollection",
+        "features": buildings.chain(roads).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn city() -> City {
+        let mut city = City::new();
+        city.insert_building(Building {
+            door: (2, 4),
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 4,
+            is_important: true,
+            id: 0,
+        });
+        city.insert_building(Building {
+            door: (10, 2),
+            x: 10,
+            y: 0,
+            width: 3,
+            height: 5,
+            is_important: false,
+            id: 1,
+        });
+        city.push_road(vec![(2, 5), (2, 6), (3, 6)], RoadTier::Arterial);
+        city.push_road(vec![(9, 2), (8, 2)], RoadTier::Local);
+        city.update_borders();
+        city.rebuild_occupancy();
+        city
+    }
+
+    #[test]
+    fn saved_cities_load_back_the_same() {
+        let city = city();
+        let path = std::env::temp_dir().join(format!("city-{}.json", std::process::id()));
+        save_city(&city, &path).unwrap();
+        let loaded = load_city(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        let buildings = |city: &City| {
+            city.building_order()
+                .iter()
+                .map(|key| city.buildings()[key].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(buildings(&loaded), buildings(&city));
+        assert_eq!(loaded.important_buildings(), city.important_buildings());
+        assert_eq!(loaded.roads(), city.roads());
+        assert_eq!(loaded.road_tiers(), city.road_tiers());
+        assert_eq!(
+            (loaded.min_x, loaded.min_y, loaded.max_x, loaded.max_y),
+            (city.min_x, city.min_y, city.max_x, city.max_y)
+        );
+        assert_eq!(loaded.is_something.len(), city.is_something.len());
+        assert_eq!(to_json(&loaded), to_json(&city));
+    }
+
+    #[test]
+    fn roads_saved_without_tiers_are_local() {
+        let json = r#"{"buildings": [], "roads": [[[0, 0], [1, 0]], [[5, 5]]]}"#;
+        let city = serde_json::from_str::<SavedCity>(json).unwrap().into_city();
+        assert_eq!(city.road_tiers(), [RoadTier::Local, RoadTier::Local]);
+    }
+
+    #[test]
+    fn svg_roads_go_through_the_center_of_their_cells() {
+        let mut city = City::new();
+        city.push_road(vec![(-179, 61), (-1, -1), (0, 0)], RoadTier::Local);
+        let svg = to_svg(&city);
+        assert!(
+            svg.contains(r#"points="-178.5,61.5 -0.5,-0.5 0.5,0.5""#),
+            "{svg}"
+        );
+    }
+}
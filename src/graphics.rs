@@ -1,7 +1,6 @@
+use image::RgbaImage;
 use pixels::{Error, Pixels, SurfaceTexture};
-use rayon::iter::{IndexedParallelIterator, ParallelIterator};
-use rayon::slice::ParallelSliceMut;
-use std::time::{Duration, Instant};
+use rand::random;
 use winit::dpi::PhysicalSize;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::EventLoop;
@@ -9,19 +8,196 @@ use winit::keyboard::KeyCode;
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::accessibility::{heat_color, AccessibilityMap};
+use crate::centrality::{cell_betweenness, centrality, SegmentGraph};
 use crate::city::City;
 use crate::city_generation::CellType;
+use crate::editing::Edit;
+use crate::export;
+use crate::road_network::RoadNetwork;
+
+mod camera;
+mod editor;
+mod minimap;
+mod regenerate;
+mod scene;
+mod text;
+mod theme;
+mod tiles;
+
+pub use camera::Camera;
+pub use editor::{Editor, Tool};
+use minimap::Minimap;
+pub use regenerate::{Parameters, Regenerator};
+use scene::{OverlayColors, Scene};
+pub use scene::{Poster, MAX_POSTER_SCALE};
+pub use theme::{blend, Theme};
+use tiles::{Frame, TileCache};
+
+/// Distance, in pixels, the mouse can move between a press and a release for it to be a click
+const CLICK_TOLERANCE: f32 = 4.0;
+/// Directory the screenshots and posters are saved to
+const OUTPUT_DIR: &str = "output";
+/// Change of the parameters of the city for each key press
+const BUILDINGS_STEP: usize = 100;
+const SPACING_STEP: i32 = 5;
+const SCALE_STEP: f64 = 0.25;
+/// Weight of the last frame in the average frame rate
+const FPS_SMOOTHING: f32 = 0.05;
+/// Color of the information text
+const INFO_COLOR: [u8; 4] = [255, 255, 255, 255];
+/// Zoom, in pixels per cell, from which the grid is drawn
+const GRID_MIN_ZOOM: f32 = 4.0;
 
 /// Representation of the application state. In this example, a box will bounce around the screen.
 pub struct CityExplorer {
-    origin: (i32, i32),
-    city: City,
+    /// Part of the city shown in the window
+    pub camera: Camera,
+    /// What the city looks like, shared with the posters being rendered
+    scene: Arc<Scene>,
     window_size: (u32, u32),
     pixels: Vec<u8>,
-    zoom_factor: f32,
+    /// Overview of the city, `None` while it has no borders
+    minimap: Option<Minimap>,
+    show_minimap: bool,
+    /// Whether the left button edits the city instead of selecting buildings
+    editing: bool,
+    editor: Editor,
+    /// Whether the cursor position, zoom, frame rate and city counts are shown
+    show_info: bool,
+    /// Frames per second, averaged over the last frames
+    fps: f32,
+    /// Position of the cursor in the window, if it is inside
+    cursor: Option<(f32, f32)>,
+    /// Tiles of the city at the zooms recently shown
+    tiles: TileCache,
+    /// Zoom and pixel of the city at the top left corner of the pixels drawn, `None` when they
+    /// must all be drawn again
+    drawn: Option<(f32, (i64, i64))>,
+    /// Roads of the city, to route between buildings
+    network: RoadNetwork,
+    /// Number of steps of the route, `None` if there is no route between the selected buildings
+    route_length: Option<usize>,
+    /// Source of new cities, if the explorer can generate them
+    regenerator: Option<Regenerator>,
+    /// Analysis shown instead of the cell types, if any
+    overlay: Option<Overlay>,
+    /// Colors of the cells for each overlay, computed the first time it is shown
+    overlay_colors: HashMap<Overlay, OverlayColors>,
+    /// Themes to pick the colors from, and the one in use
+    themes: Vec<Theme>,
+    theme: usize,
+}
+
+/// Part of the city that can be shown or hidden
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Buildings,
+    ImportantBuildings,
+    Doors,
+    Roads,
+    /// Lines between the cells, when zoomed in enough
+    Grid,
+    /// Area closer to each important building than to the others
+    Districts,
+}
+
+impl Layer {
+    /// Layers in the order of their keys, from 1 to 6
+    pub const ALL: [Self; 6] = [
+        Self::Buildings,
+        Self::ImportantBuildings,
+        Self::Doors,
+        Self::Roads,
+        Self::Grid,
+        Self::Districts,
+    ];
+}
+
+/// Analysis of the city that can be drawn over it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Overlay {
+    /// Distance by road to the closest important building
+    Accessibility,
+    /// Betweenness centrality of the roads
+    Centrality,
+}
+
+/// Keys toggling the layers, in the order of [`Layer::ALL`]
+const LAYER_KEYS: [KeyCode; 6] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+];
+
+/// Change of the parameters of the city, made smaller when shift is held
+type ParameterChange = fn(&mut Parameters, bool);
+
+/// Keys generating a new city, with the change they make to the parameters
+const REGENERATION_KEYS: [(KeyCode, ParameterChange); 6] = [
+    (KeyCode::KeyN, |p, _| p.seed = random()),
+    (KeyCode::BracketRight, |p, _| {
+        p.seed = p.seed.wrapping_add(1)
+    }),
+    (KeyCode::BracketLeft, |p, _| p.seed = p.seed.wrapping_sub(1)),
+    (KeyCode::KeyB, |p, less| {
+        p.buildings = if less {
+            p.buildings.saturating_sub(BUILDINGS_STEP)
+        } else {
+            p.buildings + BUILDINGS_STEP
+        }
+    }),
+    (KeyCode::KeyD, |p, less| {
+        p.spacing = if less {
+            (p.spacing - SPACING_STEP).max(1)
+        } else {
+            p.spacing + SPACING_STEP
+        }
+    }),
+    (KeyCode::KeyK, |p, less| {
+        p.scale = if less {
+            (p.scale - SCALE_STEP).max(SCALE_STEP)
+        } else {
+            p.scale + SCALE_STEP
+        }
+    }),
+];
+
+/// Save an image of the explorer to the output directory, named after what it is and the time
+fn save_image(image: &RgbaImage, name: &str) {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis());
+    let path = Path::new(OUTPUT_DIR).join(format!("{name}-{millis}.png"));
+    let saved = fs::create_dir_all(OUTPUT_DIR)
+        .map_err(image::ImageError::from)
+        .and_then(|()| image.save(&path));
+    match saved {
+        Ok(()) => log::info!("Saved {}", path.display()),
+        Err(e) => log::warn!("Could not save {}: {e}", path.display()),
+    }
 }
 
-pub fn start_city_explorer(city: City) -> Result<(), Error> {
+/// Explore a city in a window, drawn with the first of the themes. With a regenerator, new cities
+/// can be generated from the explorer. Posters are rendered `poster_scale` times larger than the
+/// window.
+pub fn start_city_explorer(
+    city: City,
+    themes: Vec<Theme>,
+    regenerator: Option<Regenerator>,
+    poster_scale: u32,
+) -> Result<(), Error> {
     let event_loop = EventLoop::new().unwrap();
     let mut input = WinitInputHelper::new();
     let window = {
@@ -36,7 +212,17 @@ pub fn start_city_explorer(city: City) -> Result<(), Error> {
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
         Pixels::new(window_size.width, window_size.height, surface_texture)?
     };
-    let mut city_explorer = CityExplorer::new(city, window.inner_size().into());
+    let mut city_explorer = CityExplorer::new(city, window.inner_size().into()).with_themes(themes);
+    if let Some(regenerator) = regenerator {
+        city_explorer = city_explorer.with_regenerator(regenerator);
+    }
+    window.set_title(&city_explorer.title());
+    // Distance the mouse moved since the left button was pressed
+    let mut dragged = 0.0;
+    // Whether the left button was pressed on the minimap, moving the camera instead of dragging
+    let mut on_minimap = false;
+    // Poster rendered and saved in the background, one at a time
+    let mut poster_render: Option<JoinHandle<()>> = None;
 
     let res = event_loop.run(|event, elwt| {
         // Draw the current frame
@@ -52,46 +238,171 @@ pub fn start_city_explorer(city: City) -> Result<(), Error> {
             }
         }
 
-        let mut delta_x = 0;
-        let mut delta_y = 0;
+        if city_explorer.poll_regeneration() {
+            window.set_title(&city_explorer.title());
+        }
         if input.update(&event) {
-            if input.key_pressed(KeyCode::ArrowLeft) || input.key_held(KeyCode::ArrowLeft) {
-                delta_x += -30;
+            if input.key_pressed(KeyCode::KeyH) {
+                city_explorer.toggle_overlay(Overlay::Accessibility);
+            }
+            if input.key_pressed(KeyCode::KeyC) {
+                city_explorer.toggle_overlay(Overlay::Centrality);
+            }
+            for (layer, key) in Layer::ALL.into_iter().zip(LAYER_KEYS) {
+                if input.key_pressed(key) {
+                    city_explorer.toggle_layer(layer);
+                }
+            }
+            if input.key_pressed(KeyCode::KeyT) {
+                city_explorer.next_theme();
+            }
+            if input.key_pressed(KeyCode::KeyM) {
+                city_explorer.toggle_minimap();
+            }
+            if input.key_pressed(KeyCode::KeyF) {
+                city_explorer.fit_city();
+            }
+            if input.key_pressed(KeyCode::F3) {
+                city_explorer.toggle_info();
             }
-            if input.key_pressed(KeyCode::ArrowRight) || input.key_held(KeyCode::ArrowRight) {
-                delta_x += 30;
+            let shift = input.held_shift();
+            for (key, change) in REGENERATION_KEYS {
+                if input.key_pressed(key) {
+                    city_explorer.regenerate(|p| change(p, shift));
+                    window.set_title(&city_explorer.title());
+                }
             }
-            if input.key_pressed(KeyCode::ArrowUp) || input.key_held(KeyCode::ArrowUp) {
-                delta_y += -30;
+            if input.key_pressed(KeyCode::KeyP) && !input.held_shift() {
+                save_image(&city_explorer.screenshot(), "screenshot");
             }
-            if input.key_pressed(KeyCode::ArrowDown) || input.key_held(KeyCode::ArrowDown) {
-                delta_y += 30;
+            if input.key_pressed(KeyCode::KeyP) && input.held_shift() {
+                if poster_render
+                    .as_ref()
+                    .is_some_and(|render| !render.is_finished())
+                {
+                    log::warn!("A poster is already being rendered");
+                } else {
+                    match city_explorer.poster(poster_scale) {
+                        Ok(poster) => {
+                            let (width, height) = poster.size();
+                            log::info!("Rendering a poster of {width}x{height} pixels");
+                            poster_render = Some(thread::spawn(move || {
+                                save_image(&poster.render(), "poster")
+                            }));
+                        }
+                        Err(e) => log::warn!("Could not render the poster: {e}"),
+                    }
+                }
+            }
+            if input.key_pressed(KeyCode::KeyE) {
+                city_explorer.toggle_editing();
+                window.set_title(&city_explorer.title());
+            }
+            if city_explorer.is_editing() {
+                if input.key_pressed(KeyCode::Tab) {
+                    city_explorer.next_tool();
+                    window.set_title(&city_explorer.title());
+                }
+                if input.key_pressed(KeyCode::Delete) || input.key_pressed(KeyCode::Backspace) {
+                    city_explorer.remove_selected();
+                }
+            }
+            if input.held_control() {
+                if input.key_pressed(KeyCode::KeyZ) && !input.held_shift() {
+                    city_explorer.undo();
+                }
+                if input.key_pressed(KeyCode::KeyY)
+                    || (input.key_pressed(KeyCode::KeyZ) && input.held_shift())
+                {
+                    city_explorer.redo();
+                }
+                if input.key_pressed(KeyCode::KeyS) {
+                    match city_explorer.save_city() {
+                        Ok(path) => log::info!("Saved {}", path.display()),
+                        Err(e) => log::warn!("Could not save the city: {e}"),
+                    }
+                }
             }
             if input.key_pressed(KeyCode::Escape) || input.close_requested() {
                 elwt.exit();
                 return;
             }
-        }
-        let zoom_delta = input.scroll_diff().1 * 0.05;
 
-        let speed_multiplier = if input.key_held(KeyCode::ShiftLeft) {
-            2
-        } else {
-            1
-        };
-        let zoom_adjusted_speed = (speed_multiplier as f32 / city_explorer.zoom_factor) as i32;
-        delta_x *= zoom_adjusted_speed;
-        delta_y *= zoom_adjusted_speed;
+            let dt = input.delta_time().map_or(0.0, |dt| dt.as_secs_f32());
+            let mut direction = (0.0, 0.0);
+            if input.key_held(KeyCode::ArrowLeft) {
+                direction.0 -= 1.0;
+            }
+            if input.key_held(KeyCode::ArrowRight) {
+                direction.0 += 1.0;
+            }
+            if input.key_held(KeyCode::ArrowUp) {
+                direction.1 -= 1.0;
+            }
+            if input.key_held(KeyCode::ArrowDown) {
+                direction.1 += 1.0;
+            }
+            let speed_multiplier = if input.held_shift() { 2.0 } else { 1.0 };
 
-        city_explorer.update((delta_x, delta_y), zoom_delta);
-        if delta_x != 0 || delta_y != 0 || zoom_delta != 0.0 {
-            city_explorer.redraw_pixels();
+            let cursor = input.cursor();
+            city_explorer.track_frame(dt, cursor);
+            if let Some(cursor) = cursor {
+                let notches = input.scroll_diff().1;
+                if notches != 0.0 {
+                    city_explorer.camera.zoom_at(cursor, notches);
+                }
+            }
+            // A press only selects a building if the mouse is released without dragging the city.
+            // While editing, the left button edits and the right one drags.
+            let editing = city_explorer.is_editing();
+            if input.mouse_pressed(0) {
+                dragged = 0.0;
+                on_minimap = cursor.is_some_and(|cursor| city_explorer.on_minimap(cursor));
+                if let (Some(cursor), true, false) = (cursor, editing, on_minimap) {
+                    city_explorer.edit_press(cursor);
+                }
+            }
+            if input.mouse_held(0) && on_minimap {
+                if let Some(cursor) = cursor {
+                    city_explorer.jump_to_minimap(cursor);
+                }
+            } else if input.mouse_held(0) && editing {
+                if let Some(cursor) = cursor {
+                    city_explorer.edit_drag(cursor);
+                }
+            } else if input.mouse_held(0) || input.mouse_held(1) {
+                let delta = input.cursor_diff();
+                dragged += delta.0.hypot(delta.1);
+                city_explorer.camera.drag(delta, dt);
+            }
+            if input.mouse_released(0) && !on_minimap {
+                if editing {
+                    city_explorer.edit_release();
+                } else if dragged < CLICK_TOLERANCE {
+                    if let Some(cursor) = cursor {
+                        city_explorer.click(cursor);
+                        window.set_title(&city_explorer.title());
+                    }
+                } else {
+                    city_explorer.camera.release();
+                }
+            }
+            if input.mouse_released(1) {
+                city_explorer.camera.release();
+            }
+
+            if city_explorer.camera.step(dt, direction, speed_multiplier) || dragged > 0.0 {
+                city_explorer.redraw_pixels();
+            }
+            if !input.mouse_held(0) && !input.mouse_held(1) {
+                dragged = 0.0;
+            }
         }
 
         // Resize the window
         if let Some(size) = input.window_resized() {
             city_explorer.resize(size);
-            println!("Window resized");
+            log::debug!("Window resized");
             if let Err(_err) = pixels.resize_buffer(size.width, size.height) {
                 elwt.exit();
                 return;
@@ -108,64 +419,623 @@ pub fn start_city_explorer(city: City) -> Result<(), Error> {
 
 #[allow(clippy::pedantic)]
 impl CityExplorer {
-    /// Create a new `World` instance that can draw a moving box.
-    fn new(city: City, window_size: (u32, u32)) -> Self {
+    /// Create an explorer of the city for a window of the given size
+    pub fn new(city: City, window_size: (u32, u32)) -> Self {
+        let layers = HashSet::from([
+            Layer::Buildings,
+            Layer::ImportantBuildings,
+            Layer::Doors,
+            Layer::Roads,
+        ]);
+        let themes = Theme::builtin();
         let mut res = Self {
-            city,
-            origin: (0, 0),
+            network: RoadNetwork::new(&city),
+            route_length: None,
+            overlay: None,
+            overlay_colors: HashMap::new(),
+            regenerator: None,
+            scene: Arc::new(Scene::new(city, layers, themes[0].clone())),
+            themes,
+            theme: 0,
+            camera: Camera::new(window_size),
             window_size,
             pixels: vec![0; (window_size.0 * window_size.1 * 4) as usize],
-            zoom_factor: 1.0,
+            minimap: None,
+            show_minimap: true,
+            editing: false,
+            editor: Editor::default(),
+            show_info: false,
+            fps: 0.0,
+            cursor: None,
+            tiles: TileCache::default(),
+            drawn: None,
         };
-        res.redraw_pixels();
+        res.index_city();
+        res.fit_city();
         res
     }
 
-    fn update(&mut self, delta: (i32, i32), zoom_delta: f32) {
-        self.origin.0 += delta.0 as i32;
-        self.origin.1 += delta.1 as i32;
-        self.zoom_factor += zoom_delta;
-        self.zoom_factor = self.zoom_factor.clamp(0.1, 30.0);
+    /// Let the explorer generate new cities
+    pub fn with_regenerator(self, regenerator: Regenerator) -> Self {
+        Self {
+            regenerator: Some(regenerator),
+            ..self
+        }
     }
 
-    /// There is potential to clerverly use the `delta` parameter to only redraw the pixels that have changed. But for now, it's okay as it is.
-    fn redraw_pixels(&mut self) {
-        self.pixels
-            .par_chunks_mut(4)
-            .enumerate()
-            .for_each(|(i, pixel)| {
-                let x_frame = (i % self.window_size.0 as usize) as i32;
-                let y_frame = (i / self.window_size.0 as usize) as i32;
-                let x1 = {
-                    let half_width = self.window_size.0 as f32 / 2.0;
-                    let world_offset = (x_frame as f32 - half_width) / self.zoom_factor;
-                    self.origin.0 + world_offset as i32
-                };
-                let y1 = {
-                    let half_height = self.window_size.1 as f32 / 2.0;
-                    let world_offset = (y_frame as f32 - half_height) / self.zoom_factor;
-                    self.origin.1 + world_offset as i32
+    /// Compute what is drawn from the city besides its cells
+    fn index_city(&mut self) {
+        let scene = Arc::make_mut(&mut self.scene);
+        let city = &scene.city;
+        scene.doors = city.buildings().values().map(|b| b.door).collect();
+        let important = city
+            .important_buildings()
+            .iter()
+            .map(|key| &city.buildings()[key])
+            .collect::<Vec<_>>();
+        scene.important_cells = important
+            .iter()
+            .flat_map(|b| {
+                (b.x..=b.x + b.width).flat_map(move |x| (b.y..=b.y + b.height).map(move |y| (x, y)))
+            })
+            .collect();
+        scene.district_centers = important
+            .iter()
+            .map(|b| {
+                (
+                    b.x as f32 + (b.width + 1) as f32 / 2.0,
+                    b.y as f32 + (b.height + 1) as f32 / 2.0,
+                )
+            })
+            .collect();
+        self.network = RoadNetwork::new(city);
+        self.rebuild_minimap();
+    }
+
+    /// Explore another city, framed in the window
+    pub fn replace_city(&mut self, city: City) {
+        self.scene_mut().city = city;
+        self.editor.history.clear();
+        self.city_changed();
+        self.fit_city();
+    }
+
+    /// Forget what was computed from the previous state of the city and draw it again
+    fn city_changed(&mut self) {
+        let scene = self.scene_mut();
+        scene.selected.clear();
+        scene.route.clear();
+        self.route_length = None;
+        self.editor.selected = None;
+        self.overlay_colors.clear();
+        self.index_city();
+        self.show_overlay(self.overlay);
+        self.refresh();
+    }
+
+    /// Enter the editing mode, or leave it
+    pub fn toggle_editing(&mut self) {
+        self.editing = !self.editing;
+        self.editor.selected = None;
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    /// Switch to the next editing tool
+    pub fn next_tool(&mut self) {
+        self.editor.tool = self.editor.tool.next();
+        self.editor.selected = None;
+    }
+
+    /// Start an edit with the left button pressed at a position of the window
+    pub fn edit_press(&mut self, position: (f32, f32)) {
+        let cell = self.camera.cell_at(position);
+        self.editor.press(&self.scene.city, cell);
+    }
+
+    /// Continue the edit with the mouse at a position of the window
+    pub fn edit_drag(&mut self, position: (f32, f32)) {
+        self.editor.drag(self.camera.cell_at(position));
+    }
+
+    /// Apply the edit made since the left button was pressed. Returns whether the city changed.
+    pub fn edit_release(&mut self) -> bool {
+        let edit = self.editor.release(&self.scene.city);
+        self.apply_edit(edit)
+    }
+
+    /// Remove the building selected while editing. Returns whether the city changed.
+    pub fn remove_selected(&mut self) -> bool {
+        let edit = self.editor.selected.map(|key| Edit::RemoveBuilding { key });
+        self.apply_edit(edit)
+    }
+
+    fn apply_edit(&mut self, edit: Option<Edit>) -> bool {
+        let Some(edit) = edit else {
+            return false;
+        };
+        log::debug!("{edit:?}");
+        // Follows the selected building only once the edit is made
+        let selected = self.editor.selected.map(|key| edit.key_after(key));
+        if !self
+            .editor
+            .history
+            .apply(&mut Arc::make_mut(&mut self.scene).city, edit)
+        {
+            return false;
+        }
+        self.city_changed();
+        self.editor.selected = selected.filter(|key| self.scene.city.buildings().contains_key(key));
+        true
+    }
+
+    /// Undo the last edit. Returns whether there was one.
+    pub fn undo(&mut self) -> bool {
+        let undone = self
+            .editor
+            .history
+            .undo(&mut Arc::make_mut(&mut self.scene).city);
+        if undone {
+            self.city_changed();
+        }
+        undone
+    }
+
+    /// Redo the last edit undone. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        let redone = self
+            .editor
+            .history
+            .redo(&mut Arc::make_mut(&mut self.scene).city);
+        if redone {
+            self.city_changed();
+        }
+        redone
+    }
+
+    /// Save the city, edits included, to the output directory
+    pub fn save_city(&self) -> io::Result<PathBuf> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+        let path = Path::new(OUTPUT_DIR).join(format!("city-{millis}.json"));
+        fs::create_dir_all(OUTPUT_DIR)?;
+        export::save_city(&self.scene.city, &path)?;
+        Ok(path)
+    }
+
+    /// Generate a new city with the parameters changed by `change`, in the background. Does
+    /// nothing if the explorer can't generate cities.
+    pub fn regenerate(&mut self, change: impl FnOnce(&mut Parameters)) {
+        if let Some(regenerator) = &mut self.regenerator {
+            regenerator.request(change);
+        }
+    }
+
+    /// Explore the city generated in the background, if it is ready. Returns whether the city
+    /// changed.
+    pub fn poll_regeneration(&mut self) -> bool {
+        match self.regenerator.as_mut().and_then(Regenerator::poll) {
+            Some(city) => {
+                self.replace_city(city);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Draw the city with these themes, starting with the first one
+    pub fn with_themes(mut self, themes: Vec<Theme>) -> Self {
+        if !themes.is_empty() {
+            self.themes = themes;
+            self.theme = 0;
+            self.scene_mut().theme = self.themes[0].clone();
+            self.rebuild_minimap();
+            self.refresh();
+        }
+        self
+    }
+
+    /// Theme in use
+    pub fn theme(&self) -> &Theme {
+        &self.scene.theme
+    }
+
+    /// What the city looks like, to be changed. Copied first if a poster is being rendered from
+    /// it.
+    fn scene_mut(&mut self) -> &mut Scene {
+        Arc::make_mut(&mut self.scene)
+    }
+
+    /// Switch to the next theme
+    pub fn next_theme(&mut self) {
+        self.theme = (self.theme + 1) % self.themes.len();
+        self.scene_mut().theme = self.themes[self.theme].clone();
+        log::info!("Theme: {}", self.theme().name);
+        self.rebuild_minimap();
+        self.refresh();
+    }
+
+    /// Show the minimap, or hide it if it is shown
+    pub fn toggle_minimap(&mut self) {
+        self.show_minimap = !self.show_minimap;
+    }
+
+    fn rebuild_minimap(&mut self) {
+        self.minimap = Minimap::new(&self.scene.city, self.theme());
+    }
+
+    /// Check if a position of the window is on the minimap
+    pub fn on_minimap(&self, position: (f32, f32)) -> bool {
+        self.show_minimap
+            && self
+                .minimap
+                .as_ref()
+                .is_some_and(|minimap| minimap.world_at(self.window_size, position).is_some())
+    }
+
+    /// Center the camera on the part of the city under a position of the minimap
+    pub fn jump_to_minimap(&mut self, position: (f32, f32)) {
+        let target = self
+            .minimap
+            .as_ref()
+            .and_then(|minimap| minimap.world_at(self.window_size, position));
+        if let Some(target) = target {
+            self.camera.look_at(target);
+            self.redraw_pixels();
+        }
+    }
+
+    /// Zoom and center the camera so the whole city is in view
+    pub fn fit_city(&mut self) {
+        let width = self.scene.city.max_x - self.scene.city.min_x + 1;
+        let height = self.scene.city.max_y - self.scene.city.min_y + 1;
+        if width > 0 && height > 0 {
+            let zoom = (self.window_size.0 as f32 / width as f32)
+                .min(self.window_size.1 as f32 / height as f32);
+            self.camera.set_zoom(zoom);
+            self.camera.look_at((
+                self.scene.city.min_x as f32 + width as f32 / 2.0,
+                self.scene.city.min_y as f32 + height as f32 / 2.0,
+            ));
+        }
+        self.redraw_pixels();
+    }
+
+    /// Show the information text, or hide it if it is shown
+    pub fn toggle_info(&mut self) {
+        self.show_info = !self.show_info;
+    }
+
+    /// Take into account a frame that lasted `dt` seconds, with the cursor at a position
+    pub fn track_frame(&mut self, dt: f32, cursor: Option<(f32, f32)>) {
+        if dt > 0.0 {
+            self.fps = if self.fps == 0.0 {
+                1.0 / dt
+            } else {
+                self.fps * (1.0 - FPS_SMOOTHING) + FPS_SMOOTHING / dt
+            };
+        }
+        self.cursor = cursor;
+    }
+
+    /// Lines of the information text
+    fn info_lines(&self) -> Vec<String> {
+        let cursor = self.cursor.map_or("-".to_string(), |cursor| {
+            let (x, y) = self.camera.screen_to_world(cursor);
+            format!("{x:.1}, {y:.1}")
+        });
+        let seed = self
+            .regenerator
+            .as_ref()
+            .and_then(Regenerator::current)
+            .map_or("-".to_string(), |p| p.seed.to_string());
+        let mut lines = vec![
+            format!("cursor: {cursor}"),
+            format!("zoom: {:.2}", self.camera.zoom()),
+            format!("fps: {:.0}", self.fps),
+            format!("seed: {seed}"),
+            format!("buildings: {}", self.scene.city.buildings().len()),
+            format!("roads: {}", self.scene.city.roads().len()),
+        ];
+        if self.editing {
+            lines.push(format!("editing: {:?}", self.editor.tool));
+        }
+        lines
+    }
+
+    /// Show a layer, or hide it if it is shown
+    pub fn toggle_layer(&mut self, layer: Layer) {
+        let layers = &mut self.scene_mut().layers;
+        if !layers.remove(&layer) {
+            layers.insert(layer);
+        }
+        log::info!(
+            "{layer:?}: {}",
+            if self.scene.layers.contains(&layer) {
+                "shown"
+            } else {
+                "hidden"
+            }
+        );
+        self.refresh();
+    }
+
+    /// Select the building under a position of the window, showing the route from the
+    /// previously selected one. Clicking outside of any building clears the selection.
+    pub fn click(&mut self, position: (f32, f32)) {
+        let cell = self.camera.cell_at(position);
+        let clicked = self
+            .scene
+            .city
+            .buildings_near(cell.0, cell.1, 0, 0, 0)
+            .find(|b| b.contains(cell))
+            .map(|b| (b.x, b.y));
+        let scene = Arc::make_mut(&mut self.scene);
+        if scene.selected.len() == 2 || clicked.is_none() {
+            scene.selected.clear();
+            scene.route.clear();
+            self.route_length = None;
+        }
+        if let Some(key) = clicked {
+            scene.selected.push(key);
+        }
+        if let [from, to] = self.scene.selected[..] {
+            match self.network.route_between(
+                &self.scene.city.buildings()[&from],
+                &self.scene.city.buildings()[&to],
+            ) {
+                Some((route, length)) => {
+                    self.scene_mut().route = route.into_iter().collect();
+                    self.route_length = Some(length);
+                }
+                None => self.route_length = None,
+            }
+        }
+        self.refresh();
+    }
+
+    /// Description of a building: its size, door, importance and the road leaving its door
+    fn describe(&self, key: (i32, i32)) -> String {
+        let building = &self.scene.city.buildings()[&key];
+        let (x, y) = building.door;
+        let road = self.scene.city.roads().iter().position(|road| {
+            [road.first(), road.last()]
+                .into_iter()
+                .flatten()
+                .any(|&(rx, ry)| (rx - x).abs() + (ry - y).abs() <= 1)
+        });
+        format!(
+            "Building {}{}: {}x{} at ({}, {}), door ({x}, {y}), {}",
+            building.id,
+            if building.is_important {
+                " (important)"
+            } else {
+                ""
+            },
+            building.width + 1,
+            building.height + 1,
+            building.x,
+            building.y,
+            road.map_or("no road".to_string(), |i| format!(
+                "road {i} ({:?}, {} cells)",
+                self.scene.city.road_tiers()[i],
+                self.scene.city.roads()[i].len()
+            )),
+        )
+    }
+
+    /// Title of the window, describing the last building clicked and the route to it, and the
+    /// parameters of the city
+    pub fn title(&self) -> String {
+        let selection = match self.scene.selected[..] {
+            [] => "City Explorer".to_string(),
+            [key] => self.describe(key),
+            [_, key, ..] => match self.route_length {
+                Some(length) => format!("{} | route of {length} steps", self.describe(key)),
+                None => format!("{} | no route", self.describe(key)),
+            },
+        };
+        let selection = if self.editing {
+            format!("{selection} | editing with {:?}", self.editor.tool)
+        } else {
+            selection
+        };
+        match &self.regenerator {
+            None => selection,
+            Some(regenerator) => {
+                let city = regenerator
+                    .current()
+                    .map_or("loaded city".to_string(), |p| p.to_string());
+                if regenerator.is_busy() {
+                    format!(
+                        "{selection} | {city} | generating {}...",
+                        regenerator.next()
+                    )
+                } else {
+                    format!("{selection} | {city}")
+                }
+            }
+        }
+    }
+
+    /// Show an overlay instead of the cell types, or hide it if it is already shown
+    pub fn toggle_overlay(&mut self, overlay: Overlay) {
+        self.show_overlay((self.overlay != Some(overlay)).then_some(overlay));
+        self.refresh();
+    }
+
+    /// Show an overlay, computing its colors the first time it is shown, or none
+    fn show_overlay(&mut self, overlay: Option<Overlay>) {
+        self.overlay = overlay;
+        let colors = overlay.map(|overlay| match self.overlay_colors.get(&overlay) {
+            Some(colors) => Arc::clone(colors),
+            None => {
+                let colors = Arc::new(self.compute_overlay(overlay));
+                self.overlay_colors.insert(overlay, Arc::clone(&colors));
+                colors
+            }
+        });
+        self.scene_mut().overlay = colors;
+    }
+
+    /// Colors of the cells of an overlay
+    fn compute_overlay(&self, overlay: Overlay) -> HashMap<(i32, i32), [u8; 4]> {
+        match overlay {
+            Overlay::Accessibility => {
+                AccessibilityMap::from_important_buildings(&self.scene.city, &self.network)
+                    .heatmap(&self.scene.city)
+            }
+            Overlay::Centrality => {
+                let graph = SegmentGraph::new(&self.network);
+                let scores = cell_betweenness(&graph, &centrality(&graph));
+                self.scene
+                    .city
+                    .is_something
+                    .iter()
+                    .map(|(&cell, kind)| {
+                        let color = match kind {
+                            CellType::Building => [96, 96, 96, 255],
+                            // Most of the roads see little traffic, spread their colors
+                            CellType::Road => {
+                                heat_color(scores.get(&cell).map_or(0.0, |s| s.sqrt() as f32))
+                            }
+                        };
+                        (cell, color)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Pixel of the city drawn at `zoom` at the top left corner of a frame of the given size
+    /// centered on the camera
+    fn origin(&self, zoom: f32, size: (u32, u32)) -> (i64, i64) {
+        let center = self.camera.center();
+        (
+            (center.0 * zoom - size.0 as f32 / 2.0).round() as i64,
+            (center.1 * zoom - size.1 as f32 / 2.0).round() as i64,
+        )
+    }
+
+    /// Draw the view of the camera. When it only moved, the pixels still in view are shifted and
+    /// only the uncovered ones are copied from the tiles of the city, which are rendered the first
+    /// time they are seen at a zoom. While zooming, the zoom changing every frame, the pixels are
+    /// rendered directly instead.
+    pub fn redraw_pixels(&mut self) {
+        let zoom = self.camera.zoom();
+        let origin = self.origin(zoom, self.window_size);
+        let (width, height) = (self.window_size.0 as i64, self.window_size.1 as i64);
+        // Taken out so the tiles can look at the rest of the explorer
+        let mut pixels = std::mem::take(&mut self.pixels);
+        let mut tiles = std::mem::take(&mut self.tiles);
+        let mut frame = Frame {
+            pixels: &mut pixels,
+            width: width as usize,
+        };
+        let render = |pixel| self.scene.pixel_color(pixel, zoom);
+        match self.drawn {
+            _ if self.camera.is_zooming() => frame.fill(origin, render),
+            Some((drawn_zoom, drawn_origin))
+                if drawn_zoom == zoom
+                    && (origin.0 - drawn_origin.0).abs() < width
+                    && (origin.1 - drawn_origin.1).abs() < height =>
+            {
+                let moved = (origin.0 - drawn_origin.0, origin.1 - drawn_origin.1);
+                frame.shift((-moved.0, -moved.1));
+                let (uncovered_columns, kept_columns) = if moved.0 > 0 {
+                    (width - moved.0..width, 0..width - moved.0)
+                } else {
+                    (0..-moved.0, -moved.0..width)
                 };
-                let rgba = match self.city.is_something.get(&(x1, y1)) {
-                    Some(CellType::Building) => [255, 0, 0, 255],
-                    Some(CellType::Road) => [0, 255, 0, 255],
-                    None => [0, 0, 0, 0],
+                let uncovered_rows = if moved.1 > 0 {
+                    height - moved.1..height
+                } else {
+                    0..-moved.1
                 };
+                tiles.blit(
+                    &mut frame,
+                    zoom,
+                    origin,
+                    (uncovered_columns, 0..height),
+                    render,
+                );
+                tiles.blit(
+                    &mut frame,
+                    zoom,
+                    origin,
+                    (kept_columns, uncovered_rows),
+                    render,
+                );
+            }
+            _ => tiles.blit(&mut frame, zoom, origin, (0..width, 0..height), render),
+        }
+        self.pixels = pixels;
+        self.tiles = tiles;
+        self.drawn = Some((zoom, origin));
+    }
 
-                pixel.copy_from_slice(&rgba);
-            });
+    /// Forget the tiles and draw everything again, after a change of what the city looks like
+    pub fn refresh(&mut self) {
+        self.tiles.clear();
+        self.drawn = None;
+        self.redraw_pixels();
     }
+
     /// Draw the `World` state to the frame buffer.
     ///
     /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
     fn draw(&self, frame: &mut [u8]) {
         frame.copy_from_slice(&self.pixels);
+        if let Some(minimap) = self.minimap.as_ref().filter(|_| self.show_minimap) {
+            minimap.draw(frame, self.window_size, &self.camera);
+        }
+        if self.editing {
+            self.editor.draw(
+                frame,
+                self.window_size,
+                &self.camera,
+                &self.scene.city,
+                self.theme(),
+            );
+        }
+        if self.show_info {
+            text::draw_lines(
+                frame,
+                self.window_size.0 as usize,
+                &self.info_lines(),
+                INFO_COLOR,
+            );
+        }
+    }
+
+    /// Pixels of the window as they are shown
+    pub fn screenshot(&self) -> RgbaImage {
+        let mut frame = vec![0; self.pixels.len()];
+        self.draw(&mut frame);
+        RgbaImage::from_raw(self.window_size.0, self.window_size.1, frame).unwrap()
+    }
+
+    /// What the camera shows, to be rendered `scale` times larger than the window, without the
+    /// minimap. Fails if the poster would be too large.
+    pub fn poster(&self, scale: u32) -> Result<Poster, String> {
+        Poster::new(
+            Arc::clone(&self.scene),
+            self.window_size,
+            self.camera.zoom(),
+            scale,
+            |zoom, size| self.origin(zoom, size),
+        )
     }
 
     fn resize(&mut self, new_size: PhysicalSize<u32>) {
         self.window_size = (new_size.width, new_size.height);
+        self.camera.resize(self.window_size);
         self.pixels
             .resize((self.window_size.0 * self.window_size.1 * 4) as usize, 0);
+        self.drawn = None;
         self.redraw_pixels();
     }
 }
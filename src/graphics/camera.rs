@@ -0,0 +1,166 @@
+/// Zoom factor applied by one notch of the mouse wheel
+const ZOOM_STEP: f32 = 1.15;
+/// Bounds of the zoom, in pixels per cell
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 30.0;
+/// How fast the zoom reaches its target, per second
+const ZOOM_SMOOTHING: f32 = 15.0;
+/// Speed of the keyboard panning, in pixels per second
+const PAN_SPEED: f32 = 900.0;
+/// How fast the camera slows down once released, per second
+const FRICTION: f32 = 5.0;
+/// Speed, in pixels per second, under which the camera stops
+const MIN_SPEED: f32 = 10.0;
+
+/// Part of the city shown in the window.
+///
+/// Positions in the window are in pixels from its top left corner, positions in the city are in
+/// cells, the center of the window looking at `center`.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    /// Position of the city at the center of the window
+    center: (f32, f32),
+    /// Pixels per cell
+    zoom: f32,
+    /// Zoom the camera is smoothly moving to
+    target_zoom: f32,
+    /// Position in the window that stays still while zooming
+    zoom_anchor: (f32, f32),
+    /// Speed of the camera gliding after a drag, in cells per second
+    velocity: (f32, f32),
+    /// Speed of the last drag, in cells per second
+    drag_velocity: (f32, f32),
+    /// Size of the window
+    viewport: (f32, f32),
+}
+
+#[allow(clippy::pedantic)]
+impl Camera {
+    pub fn new(viewport: (u32, u32)) -> Self {
+        Self {
+            center: (0.0, 0.0),
+            zoom: 1.0,
+            target_zoom: 1.0,
+            zoom_anchor: (0.0, 0.0),
+            velocity: (0.0, 0.0),
+            drag_velocity: (0.0, 0.0),
+            viewport: (viewport.0 as f32, viewport.1 as f32),
+        }
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        self.center
+    }
+
+    /// Check if the camera is still moving towards the zoom it was given
+    pub fn is_zooming(&self) -> bool {
+        self.zoom != self.target_zoom
+    }
+
+    /// Update the size of the window
+    pub fn resize(&mut self, viewport: (u32, u32)) {
+        self.viewport = (viewport.0 as f32, viewport.1 as f32);
+    }
+
+    /// Position of the city under a position of the window
+    pub fn screen_to_world(&self, position: (f32, f32)) -> (f32, f32) {
+        (
+            self.center.0 + (position.0 - self.viewport.0 / 2.0) / self.zoom,
+            self.center.1 + (position.1 - self.viewport.1 / 2.0) / self.zoom,
+        )
+    }
+
+    /// Position of the window over a position of the city
+    pub fn world_to_screen(&self, position: (f32, f32)) -> (f32, f32) {
+        (
+            (position.0 - self.center.0) * self.zoom + self.viewport.0 / 2.0,
+            (position.1 - self.center.1) * self.zoom + self.viewport.1 / 2.0,
+        )
+    }
+
+    /// Cell of the city under a position of the window
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn cell_at(&self, position: (f32, f32)) -> (i32, i32) {
+        let (x, y) = self.screen_to_world(position);
+        (x.floor() as i32, y.floor() as i32)
+    }
+
+    /// Center the window on a position of the city, stopping the camera
+    pub fn look_at(&mut self, center: (f32, f32)) {
+        self.center = center;
+        self.velocity = (0.0, 0.0);
+    }
+
+    /// Set the zoom at once, around the center of the window
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.target_zoom = self.zoom;
+    }
+
+    /// Zoom by mouse wheel notches, keeping the city under `anchor` in place
+    pub fn zoom_at(&mut self, anchor: (f32, f32), notches: f32) {
+        self.target_zoom = (self.target_zoom * ZOOM_STEP.powf(notches)).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.zoom_anchor = anchor;
+    }
+
+    /// Follow the mouse dragging the city by `delta` pixels during `dt` seconds
+    pub fn drag(&mut self, delta: (f32, f32), dt: f32) {
+        let moved = (-delta.0 / self.zoom, -delta.1 / self.zoom);
+        self.center.0 += moved.0;
+        self.center.1 += moved.1;
+        self.velocity = (0.0, 0.0);
+        if dt > 0.0 {
+            self.drag_velocity = (moved.0 / dt, moved.1 / dt);
+        }
+    }
+
+    /// Let the camera glide at the speed of the drag that just ended
+    pub fn release(&mut self) {
+        self.velocity = self.drag_velocity;
+        self.drag_velocity = (0.0, 0.0);
+    }
+
+    /// Move the camera for `dt` seconds: pan in the direction of the keyboard, glide after a drag
+    /// and zoom towards the target. Returns whether the view changed.
+    pub fn step(&mut self, dt: f32, direction: (f32, f32), speed_multiplier: f32) -> bool {
+        let mut moved = false;
+        if direction != (0.0, 0.0) {
+            let speed = PAN_SPEED * speed_multiplier / self.zoom;
+            self.center.0 += direction.0 * speed * dt;
+            self.center.1 += direction.1 * speed * dt;
+            self.velocity = (0.0, 0.0);
+            moved = true;
+        }
+
+        if self.velocity != (0.0, 0.0) {
+            self.center.0 += self.velocity.0 * dt;
+            self.center.1 += self.velocity.1 * dt;
+            let decay = (-FRICTION * dt).exp();
+            self.velocity = (self.velocity.0 * decay, self.velocity.1 * decay);
+            if self.velocity.0.hypot(self.velocity.1) * self.zoom < MIN_SPEED {
+                self.velocity = (0.0, 0.0);
+            }
+            moved = true;
+        }
+
+        if self.zoom != self.target_zoom {
+            let anchored = self.screen_to_world(self.zoom_anchor);
+            let remaining = (self.zoom - self.target_zoom) * (-ZOOM_SMOOTHING * dt).exp();
+            self.zoom = if (remaining / self.target_zoom).abs() < 1e-3 {
+                self.target_zoom
+            } else {
+                self.target_zoom + remaining
+            };
+            // Move the center so the anchored position is under the anchor again
+            let drifted = self.screen_to_world(self.zoom_anchor);
+            self.center.0 += anchored.0 - drifted.0;
+            self.center.1 += anchored.1 - drifted.1;
+            moved = true;
+        }
+        moved
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{
+    city::City,
+    editing::{Edit, History},
+    straighten::digital_line,
+};
+
+use super::{Camera, Theme};
+
+/// What the left button does while editing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    /// Select a building, drag it to move it or drag its bottom right corner to resize it
+    Select,
+    /// Drag a rectangle to place a building
+    Building,
+    /// Drag to draw a road
+    Road,
+    /// Drag over roads to erase them
+    Erase,
+}
+
+impl Tool {
+    /// Tool after this one, going back to the first after the last
+    pub fn next(self) -> Self {
+        match self {
+            Self::Select => Self::Building,
+            Self::Building => Self::Road,
+            Self::Road => Self::Erase,
+            Self::Erase => Self::Select,
+        }
+    }
+}
+
+/// Building held by the select tool
+#[derive(Clone, Copy, Debug)]
+enum Grab {
+    Move((i32, i32)),
+    Resize((i32, i32)),
+}
+
+/// State of the editing mode of the explorer: the tool, the stroke of the mouse being dragged,
+/// and the edits that can be undone
+pub struct Editor {
+    pub tool: Tool,
+    pub history: History,
+    /// Key of the building selected by the select tool
+    pub selected: Option<(i32, i32)>,
+    /// Cells the mouse went over since the left button was pressed, without gaps
+    stroke: Vec<(i32, i32)>,
+    grab: Option<Grab>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            tool: Tool::Select,
+            history: History::default(),
+            selected: None,
+            stroke: vec![],
+            grab: None,
+        }
+    }
+}
+
+impl Editor {
+    /// Start a stroke on a cell
+    pub fn press(&mut self, city: &City, cell: (i32, i32)) {
+        self.stroke = vec![cell];
+        if self.tool == Tool::Select {
+            let building = city
+                .buildings_near(cell.0, cell.1, 0, 0, 0)
+                .find(|b| b.contains(cell));
+            self.selected = building.map(|b| (b.x, b.y));
+            self.grab = building.map(|b| {
+                if cell == (b.x + b.width, b.y + b.height) {
+                    Grab::Resize((b.x, b.y))
+                } else {
+                    Grab::Move((b.x, b.y))
+                }
+            });
+        }
+    }
+
+    /// Continue the stroke to a cell
+    pub fn drag(&mut self, cell: (i32, i32)) {
+        if let Some(&last) = self.stroke.last() {
+            if last != cell {
+                self.stroke
+                    .extend(digital_line(last, cell).into_iter().skip(1));
+            }
+        }
+    }
+
+    /// End the stroke, giving the edit it makes, if any
+    pub fn release(&mut self, city: &City) -> Option<Edit> {
+        let stroke = std::mem::take(&mut self.stroke);
+        let grab = self.grab.take();
+        let (&start, &end) = (stroke.first()?, stroke.last()?);
+        match self.tool {
+            Tool::Select => match grab? {
+                Grab::Move(key) => {
+                    let by = (end.0 - start.0, end.1 - start.1);
+                    Some(Edit::MoveBuilding { key, by })
+                }
+                Grab::Resize(key) => {
+                    let building = city.buildings().get(&key)?;
+                    Some(Edit::ResizeBuilding {
+                        key,
+                        width: end.0 - building.x,
+                        height: end.1 - building.y,
+                    })
+                }
+            },
+            Tool::Building => Some(Edit::PlaceBuilding {
+                x: start.0.min(end.0),
+                y: start.1.min(end.1),
+                width: (end.0 - start.0).abs(),
+                height: (end.1 - start.1).abs(),
+            }),
+            Tool::Road => Some(Edit::DrawRoad(stroke)),
+            Tool::Erase => Some(Edit::EraseRoad(stroke)),
+        }
+    }
+
+    /// Draw what the stroke would do, and the selected building, over a frame of the window
+    pub fn draw(
+        &self,
+        frame: &mut [u8],
+        window_size: (u32, u32),
+        camera: &Camera,
+        city: &City,
+        theme: &Theme,
+    ) {
+        let mut painter = Painter {
+            frame,
+            window_size,
+            camera,
+        };
+        let start = self.stroke.first().copied().unwrap_or_default();
+        let end = self.stroke.last().copied().unwrap_or_default();
+        let outline = theme.selected_building;
+        match (self.tool, self.grab) {
+            (Tool::Select, Some(Grab::Move(key))) => {
+                if let Some(b) = city.buildings().get(&key) {
+                    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                    painter.outline(
+                        (b.x + dx, b.y + dy),
+                        (b.x + b.width + dx, b.y + b.height + dy),
+                        outline,
+                    );
+                }
+            }
+            (Tool::Select, Some(Grab::Resize(key))) => {
+                if let Some(b) = city.buildings().get(&key) {
+                    painter.outline((b.x, b.y), end, outline);
+                }
+            }
+            (Tool::Select, None) => {
+                if let Some(b) = self.selected.and_then(|key| city.buildings().get(&key)) {
+                    painter.outline((b.x, b.y), (b.x + b.width, b.y + b.height), outline);
+                }
+            }
+            (Tool::Building, _) if !self.stroke.is_empty() => {
+                painter.outline(
+                    (start.0.min(end.0), start.1.min(end.1)),
+                    (start.0.max(end.0), start.1.max(end.1)),
+                    outline,
+                );
+            }
+            (Tool::Road, _) => {
+                for &cell in &self.stroke {
+                    painter.fill(cell, theme.road);
+                }
+            }
+            (Tool::Erase, _) => {
+                for &cell in &self.stroke {
+                    painter.fill(cell, theme.background);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draws cells of the city over a frame of the window
+struct Painter<'a> {
+    frame: &'a mut [u8],
+    window_size: (u32, u32),
+    camera: &'a Camera,
+}
+
+#[allow(clippy::pedantic)]
+impl Painter<'_> {
+    fn put(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if (0..i64::from(self.window_size.0)).contains(&x)
+            && (0..i64::from(self.window_size.1)).contains(&y)
+        {
+            let i = ((y * i64::from(self.window_size.0) + x) * 4) as usize;
+            self.frame[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    /// Pixels of the window covered by the cells from `min` to `max`, both included, clamped
+    /// to the window
+    fn bounds(&self, min: (i32, i32), max: (i32, i32)) -> (i64, i64, i64, i64) {
+        let (left, top) = self.camera.world_to_screen((min.0 as f32, min.1 as f32));
+        let (right, bottom) = self
+            .camera
+            .world_to_screen(((max.0 + 1) as f32, (max.1 + 1) as f32));
+        let clamp = |v: f32, size: u32| (v.round() as i64).clamp(-1, i64::from(size));
+        (
+            clamp(left, self.window_size.0),
+            clamp(top, self.window_size.1),
+            clamp(right, self.window_size.0) - 1,
+            clamp(bottom, self.window_size.1) - 1,
+        )
+    }
+
+    /// Outline the cells from `min` to `max`, both included
+    fn outline(&mut self, min: (i32, i32), max: (i32, i32), color: [u8; 4]) {
+        let (left, top, right, bottom) = self.bounds(min, max);
+        for x in left..=right {
+            self.put(x, top, color);
+            self.put(x, bottom, color);
+        }
+        for y in top..=bottom {
+            self.put(left, y, color);
+            self.put(right, y, color);
+        }
+    }
+
+    /// Fill a cell, at least one pixel
+    fn fill(&mut self, cell: (i32, i32), color: [u8; 4]) {
+        let (left, top, right, bottom) = self.bounds(cell, cell);
+        for y in top..=bottom.max(top) {
+            for x in left..=right.max(left) {
+                self.put(x, y, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{city::City, city_generation::CellType};
+
+use super::{Camera, Theme};
+
+/// Largest width and height of the minimap, in pixels
+const SIZE: f32 = 200.0;
+/// Space between the minimap and the corner of the window, in pixels
+const MARGIN: u32 = 10;
+
+/// Whole city drawn small in the bottom right corner of the window, with the part of it shown
+/// by the camera
+pub struct Minimap {
+    pixels: Vec<u8>,
+    size: (u32, u32),
+    /// Cell at the top left corner of the minimap
+    min: (i32, i32),
+    /// Pixels per cell
+    scale: f32,
+    /// Color of the border and of the view of the camera
+    outline: [u8; 4],
+}
+
+#[allow(clippy::pedantic)]
+impl Minimap {
+    /// Draw the city inside its borders, `None` if it has no borders yet
+    pub fn new(city: &City, theme: &Theme) -> Option<Self> {
+        let width = city.max_x - city.min_x + 1;
+        let height = city.max_y - city.min_y + 1;
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let scale = SIZE / width.max(height) as f32;
+        let size = (
+            ((width as f32 * scale) as u32).max(1),
+            ((height as f32 * scale) as u32).max(1),
+        );
+        let mut minimap = Self {
+            pixels: theme.background.repeat((size.0 * size.1) as usize),
+            size,
+            min: (city.min_x, city.min_y),
+            scale,
+            outline: theme.selected_building,
+        };
+        // Roads last so the thin ones aren't hidden by the buildings next to them
+        for (roads, color) in [(false, theme.building), (true, theme.road)] {
+            for (&cell, kind) in &city.is_something {
+                if matches!(kind, CellType::Road) != roads {
+                    continue;
+                }
+                let x = ((cell.0 - minimap.min.0) as f32 * scale) as u32;
+                let y = ((cell.1 - minimap.min.1) as f32 * scale) as u32;
+                if x < size.0 && y < size.1 {
+                    let i = ((y * size.0 + x) * 4) as usize;
+                    minimap.pixels[i..i + 4].copy_from_slice(&color);
+                }
+            }
+        }
+        Some(minimap)
+    }
+
+    /// Top left corner of the minimap in a window, `None` if the window is too small for it
+    fn corner(&self, window_size: (u32, u32)) -> Option<(u32, u32)> {
+        Some((
+            window_size.0.checked_sub(self.size.0 + MARGIN)?,
+            window_size.1.checked_sub(self.size.1 + MARGIN)?,
+        ))
+    }
+
+    /// Position of the city under a position of the window, if it is on the minimap
+    pub fn world_at(&self, window_size: (u32, u32), position: (f32, f32)) -> Option<(f32, f32)> {
+        let corner = self.corner(window_size)?;
+        let x = position.0 - corner.0 as f32;
+        let y = position.1 - corner.1 as f32;
+        ((0.0..self.size.0 as f32).contains(&x) && (0.0..self.size.1 as f32).contains(&y)).then(
+            || {
+                (
+                    self.min.0 as f32 + x / self.scale,
+                    self.min.1 as f32 + y / self.scale,
+                )
+            },
+        )
+    }
+
+    /// Draw the minimap over a frame of the window, with the outline of what the camera shows
+    pub fn draw(&self, frame: &mut [u8], window_size: (u32, u32), camera: &Camera) {
+        let Some(corner) = self.corner(window_size) else {
+            return;
+        };
+        let row_length = (self.size.0 * 4) as usize;
+        for y in 0..self.size.1 {
+            let from = (y * self.size.0 * 4) as usize;
+            let to = (((corner.1 + y) * window_size.0 + corner.0) * 4) as usize;
+            frame[to..to + row_length].copy_from_slice(&self.pixels[from..from + row_length]);
+        }
+
+        let mut put = |x: i64, y: i64| {
+            if (0..self.size.0 as i64).contains(&x) && (0..self.size.1 as i64).contains(&y) {
+                let i = (((corner.1 as i64 + y) * window_size.0 as i64 + corner.0 as i64 + x) * 4)
+                    as usize;
+                frame[i..i + 4].copy_from_slice(&self.outline);
+            }
+        };
+        let mut rectangle = |left: i64, top: i64, right: i64, bottom: i64| {
+            for x in left..=right {
+                put(x, top);
+                put(x, bottom);
+            }
+            for y in top..=bottom {
+                put(left, y);
+                put(right, y);
+            }
+        };
+        rectangle(0, 0, self.size.0 as i64 - 1, self.size.1 as i64 - 1);
+        let to_minimap = |(x, y): (f32, f32)| {
+            (
+                ((x - self.min.0 as f32) * self.scale).floor() as i64,
+                ((y - self.min.1 as f32) * self.scale).floor() as i64,
+            )
+        };
+        let (left, top) = to_minimap(camera.screen_to_world((0.0, 0.0)));
+        let (right, bottom) =
+            to_minimap(camera.screen_to_world((window_size.0 as f32, window_size.1 as f32)));
+        // Clamped so a view larger than the minimap still shows its edges inside it
+        let max = (self.size.0 as i64 - 1, self.size.1 as i64 - 1);
+        if right < 0 || bottom < 0 || left > max.0 || top > max.1 {
+            return;
+        }
+        rectangle(
+            left.clamp(0, max.0),
+            top.clamp(0, max.1),
+            right.clamp(0, max.0),
+            bottom.clamp(0, max.1),
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+};
+
+use crate::city::City;
+
+/// Parameters of the city that can be changed from the explorer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameters {
+    pub seed: u64,
+    /// Number of normal buildings
+    pub buildings: usize,
+    /// Smallest distance between a new building and the one it is placed next to
+    pub spacing: i32,
+    /// Scale of the important buildings
+    pub scale: f64,
+}
+
+impl fmt::Display for Parameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed {}, {} buildings, spacing {}, scale {}",
+            self.seed, self.buildings, self.spacing, self.scale
+        )
+    }
+}
+
+/// What the explorer and the worker share
+#[derive(Default)]
+struct State {
+    /// Parameters of the latest city asked for, until the worker starts generating it
+    request: Option<Parameters>,
+    /// Latest city generated, until the explorer picks it up
+    result: Option<(Parameters, City)>,
+    /// Whether the worker is generating a city
+    working: bool,
+    /// Whether the explorer is gone and the worker should stop
+    closed: bool,
+}
+
+type Shared = Arc<(Mutex<State>, Condvar)>;
+
+/// Generate the cities asked for one at a time, only keeping the latest request while a city is
+/// being generated
+fn work(shared: &Shared, generate: &(dyn Fn(Parameters) -> City + Send + Sync)) {
+    let (state, wake) = &**shared;
+    loop {
+        let parameters = {
+            let mut state = state.lock().unwrap();
+            while state.request.is_none() && !state.closed {
+                state = wake.wait(state).unwrap();
+            }
+            if state.closed {
+                return;
+            }
+            state.working = true;
+            state.request.take().unwrap()
+        };
+        let city = panic::catch_unwind(AssertUnwindSafe(|| generate(parameters)));
+        let mut state = state.lock().unwrap();
+        state.working = false;
+        match city {
+            // A city for older parameters than the ones asked for since is of no use
+            Ok(city) if state.request.is_none() => state.result = Some((parameters, city)),
+            Ok(_) => {}
+            Err(_) => log::warn!("The generation of the city with {parameters} failed"),
+        }
+    }
+}
+
+/// Generates new cities for the explorer on a background thread
+pub struct Regenerator {
+    /// Parameters of the city shown, `None` if it wasn't generated here (loaded from a file)
+    current: Option<Parameters>,
+    /// Parameters the next city is generated with
+    next: Parameters,
+    shared: Shared,
+}
+
+impl Regenerator {
+    /// Generate the cities with `generate`, starting from `parameters`. `generated` tells if the
+    /// city shown was generated with them.
+    pub fn new(
+        parameters: Parameters,
+        generated: bool,
+        generate: impl Fn(Parameters) -> City + Send + Sync + 'static,
+    ) -> Self {
+        let shared = Shared::default();
+        let worker = Arc::clone(&shared);
+        thread::spawn(move || work(&worker, &generate));
+        Self {
+            current: generated.then_some(parameters),
+            next: parameters,
+            shared,
+        }
+    }
+
+    /// Parameters of the city shown, `None` if it wasn't generated here
+    pub fn current(&self) -> Option<Parameters> {
+        self.current
+    }
+
+    /// Parameters the next city is generated with
+    pub fn next(&self) -> Parameters {
+        self.next
+    }
+
+    /// State shared with the worker, locked
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.shared.0.lock().unwrap()
+    }
+
+    /// Check if a city is being generated
+    pub fn is_busy(&self) -> bool {
+        let state = self.state();
+        state.working || state.request.is_some()
+    }
+
+    /// Ask for a city with the parameters changed by `change`. It replaces the request not
+    /// started yet, if any, the city being generated still being finished.
+    pub fn request(&mut self, change: impl FnOnce(&mut Parameters)) {
+        change(&mut self.next);
+        log::info!("Generating a city with {}", self.next);
+        let mut state = self.state();
+        state.request = Some(self.next);
+        state.result = None;
+        self.shared.1.notify_one();
+    }
+
+    /// City generated since the last call with the latest parameters asked for, if any
+    pub fn poll(&mut self) -> Option<City> {
+        let (parameters, city) = self.state().result.take()?;
+        if parameters != self.next {
+            return None;
+        }
+        self.current = Some(parameters);
+        Some(city)
+    }
+}
+
+impl Drop for Regenerator {
+    /// Let the worker stop once done with the city it is generating, if any
+    fn drop(&mut self) {
+        self.state().closed = true;
+        self.shared.1.notify_one();
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use image::RgbaImage;
+
+use crate::city::City;
+use crate::city_generation::CellType;
+
+use super::theme::{blend, Theme};
+use super::tiles::Frame;
+use super::{Layer, GRID_MIN_ZOOM};
+
+/// Largest number of times a poster can be larger than the window
+pub const MAX_POSTER_SCALE: u32 = 16;
+/// Largest number of pixels of a poster, 512 MB of pixels
+const MAX_POSTER_PIXELS: u64 = 1 << 27;
+
+/// Colors of the cells of an overlay, shared by the scenes showing it
+pub(super) type OverlayColors = Arc<HashMap<(i32, i32), [u8; 4]>>;
+
+/// The city and everything deciding the colors of its pixels. Shared with the posters rendered in
+/// the background, and copied if it changes while one is.
+#[derive(Clone)]
+pub(super) struct Scene {
+    pub city: City,
+    /// Layers drawn
+    pub layers: HashSet<Layer>,
+    /// Theme in use
+    pub theme: Theme,
+    /// Colors of the cells of the overlay shown instead of the cell types, if any
+    pub overlay: Option<OverlayColors>,
+    /// Keys of the buildings clicked, the last one being inspected, and the route between them
+    /// shown when there are two
+    pub selected: Vec<(i32, i32)>,
+    /// Cells of the route between the selected buildings
+    pub route: HashSet<(i32, i32)>,
+    /// Doors of the buildings, which aren't in the occupancy map
+    pub doors: HashSet<(i32, i32)>,
+    /// Cells of the important buildings
+    pub important_cells: HashSet<(i32, i32)>,
+    /// Centers of the important buildings, each cell belonging to the district of the closest
+    pub district_centers: Vec<(f32, f32)>,
+}
+
+#[allow(clippy::pedantic)]
+impl Scene {
+    pub fn new(city: City, layers: HashSet<Layer>, theme: Theme) -> Self {
+        Self {
+            city,
+            layers,
+            theme,
+            overlay: None,
+            selected: vec![],
+            route: HashSet::new(),
+            doors: HashSet::new(),
+            important_cells: HashSet::new(),
+            district_centers: vec![],
+        }
+    }
+
+    /// Color of the district of the closest important building to a position of the city
+    fn district_color(&self, position: (f32, f32)) -> Option<[u8; 4]> {
+        let districts = &self.theme.districts;
+        if districts.is_empty() {
+            return None;
+        }
+        self.district_centers
+            .iter()
+            .map(|&(x, y)| (x - position.0).powi(2) + (y - position.1).powi(2))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| districts[i % districts.len()])
+    }
+
+    /// Color of a cell, according to the layers shown
+    fn cell_color(&self, cell: (i32, i32)) -> [u8; 4] {
+        let theme = &self.theme;
+        let shown = |layer| self.layers.contains(&layer);
+        let background = if shown(Layer::Districts) {
+            let center = (cell.0 as f32 + 0.5, cell.1 as f32 + 0.5);
+            self.district_color(center)
+                .map_or(theme.background, |color| blend(theme.background, color))
+        } else {
+            theme.background
+        };
+        if shown(Layer::Doors) && self.doors.contains(&cell) {
+            return theme.door;
+        }
+        match self.city.is_something.get(&cell) {
+            Some(CellType::Building) => {
+                let important = self.important_cells.contains(&cell);
+                let layer = if important {
+                    Layer::ImportantBuildings
+                } else {
+                    Layer::Buildings
+                };
+                if !shown(layer) {
+                    background
+                } else if self
+                    .selected
+                    .iter()
+                    .any(|key| self.city.buildings()[key].contains(cell))
+                {
+                    theme.selected_building
+                } else if important {
+                    theme.important_building
+                } else {
+                    theme.building
+                }
+            }
+            Some(CellType::Road) if shown(Layer::Roads) => theme.road,
+            _ => background,
+        }
+    }
+
+    /// Color of a pixel of the city drawn at `zoom`
+    pub fn pixel_color(&self, pixel: (i64, i64), zoom: f32) -> [u8; 4] {
+        let theme = &self.theme;
+        let position = (pixel.0 as f32 / zoom, pixel.1 as f32 / zoom);
+        let cell = (position.0.floor() as i32, position.1.floor() as i32);
+        let rgba = if self.route.contains(&cell) {
+            theme.route
+        } else if let Some(colors) = &self.overlay {
+            colors.get(&cell).copied().unwrap_or(theme.background)
+        } else {
+            self.cell_color(cell)
+        };
+        let show_grid = self.layers.contains(&Layer::Grid) && zoom >= GRID_MIN_ZOOM;
+        // The first pixel of each cell on both axes
+        let on_grid_line = || {
+            (pixel.0 as f32 / zoom).floor() != ((pixel.0 - 1) as f32 / zoom).floor()
+                || (pixel.1 as f32 / zoom).floor() != ((pixel.1 - 1) as f32 / zoom).floor()
+        };
+        if show_grid && on_grid_line() {
+            blend(rgba, theme.grid)
+        } else {
+            rgba
+        }
+    }
+}
+
+/// What the camera showed when the poster was asked for, to be rendered on another thread
+pub struct Poster {
+    scene: Arc<Scene>,
+    size: (u32, u32),
+    zoom: f32,
+    /// Pixel of the city at the top left corner of the poster
+    origin: (i64, i64),
+}
+
+impl Poster {
+    /// Poster of the scene seen through a window of the given size, `scale` times larger than
+    /// it. `origin` gives the pixel of the city at the top left corner of a frame of a size at a
+    /// zoom.
+    pub(super) fn new(
+        scene: Arc<Scene>,
+        window_size: (u32, u32),
+        zoom: f32,
+        scale: u32,
+        origin: impl Fn(f32, (u32, u32)) -> (i64, i64),
+    ) -> Result<Self, String> {
+        if scale == 0 || scale > MAX_POSTER_SCALE {
+            return Err(format!(
+                "posters are 1 to {MAX_POSTER_SCALE} times larger than the window, not {scale}"
+            ));
+        }
+        let (width, height) = (
+            u64::from(window_size.0) * u64::from(scale),
+            u64::from(window_size.1) * u64::from(scale),
+        );
+        let fits = width
+            .checked_mul(height)
+            .is_some_and(|pixels| pixels <= MAX_POSTER_PIXELS);
+        let (Ok(w), Ok(h), true) = (u32::try_from(width), u32::try_from(height), fits) else {
+            return Err(format!(
+                "a poster of {width}x{height} pixels is larger than {MAX_POSTER_PIXELS} pixels"
+            ));
+        };
+        let size = (w, h);
+        #[allow(clippy::cast_precision_loss)]
+        let zoom = zoom * scale as f32;
+        Ok(Self {
+            scene,
+            size,
+            zoom,
+            origin: origin(zoom, size),
+        })
+    }
+
+    /// Width and height of the poster, in pixels
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Render every pixel of the poster
+    pub fn render(&self) -> RgbaImage {
+        // Fits, as the number of pixels is capped
+        let mut pixels = vec![0; self.size.0 as usize * self.size.1 as usize * 4];
+        Frame {
+            pixels: &mut pixels,
+            width: self.size.0 as usize,
+        }
+        .fill(self.origin, |pixel| {
+            self.scene.pixel_color(pixel, self.zoom)
+        });
+        RgbaImage::from_raw(self.size.0, self.size.1, pixels)
+            .expect("the buffer has the size of the poster")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene() -> Arc<Scene> {
+        Arc::new(Scene::new(
+            City::new(),
+            HashSet::new(),
+            Theme::builtin().remove(0),
+        ))
+    }
+
+    #[test]
+    fn poster_is_scaled_and_rendered() {
+        let poster = Poster::new(scene(), (30, 20), 2.0, 4, |_, _| (0, 0)).unwrap();
+        assert_eq!(poster.size(), (120, 80));
+        let image = poster.render();
+        assert_eq!(image.dimensions(), (120, 80));
+        let background = scene().theme.background;
+        assert!(image.pixels().all(|pixel| pixel.0 == background));
+    }
+
+    #[test]
+    fn oversized_posters_are_rejected() {
+        for scale in [0, MAX_POSTER_SCALE + 1] {
+            assert!(Poster::new(scene(), (30, 20), 1.0, scale, |_, _| (0, 0)).is_err());
+        }
+        let huge = (u32::MAX, u32::MAX);
+        assert!(Poster::new(scene(), huge, 1.0, MAX_POSTER_SCALE, |_, _| (0, 0)).is_err());
+        assert!(Poster::new(scene(), (20_000, 20_000), 1.0, 1, |_, _| (0, 0)).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// Colors the explorer draws the city with.
+///
+/// Colors are RGBA, the alpha of the grid and district colors being used to blend them over the
+/// background. Missing colors of a theme loaded from a file are taken from the classic theme.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub background: [u8; 4],
+    pub building: [u8; 4],
+    pub important_building: [u8; 4],
+    pub selected_building: [u8; 4],
+    pub door: [u8; 4],
+    pub road: [u8; 4],
+    pub route: [u8; 4],
+    pub grid: [u8; 4],
+    /// Colors of the districts, cycled through when there are more important buildings
+    pub districts: Vec<[u8; 4]>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl Theme {
+    /// Saturated colors on black
+    pub fn classic() -> Self {
+        Self {
+            name: "classic".to_string(),
+            background: [0, 0, 0, 255],
+            building: [255, 0, 0, 255],
+            important_building: [255, 160, 0, 255],
+            selected_building: [255, 255, 255, 255],
+            door: [0, 128, 255, 255],
+            road: [0, 255, 0, 255],
+            route: [255, 255, 0, 255],
+            grid: [255, 255, 255, 40],
+            districts: vec![
+                [255, 0, 255, 70],
+                [0, 255, 255, 70],
+                [255, 255, 0, 70],
+                [0, 128, 255, 70],
+                [255, 128, 0, 70],
+                [128, 255, 0, 70],
+            ],
+        }
+    }
+
+    /// Okabe and Ito's palette, told apart with any color vision deficiency
+    pub fn colorblind() -> Self {
+        Self {
+            name: "colorblind".to_string(),
+            background: [20, 20, 20, 255],
+            building: [213, 94, 0, 255],
+            important_building: [230, 159, 0, 255],
+            selected_building: [255, 255, 255, 255],
+            door: [86, 180, 233, 255],
+            road: [0, 114, 178, 255],
+            route: [240, 228, 66, 255],
+            grid: [255, 255, 255, 40],
+            districts: vec![
+                [0, 158, 115, 90],
+                [204, 121, 167, 90],
+                [86, 180, 233, 90],
+                [240, 228, 66, 90],
+                [230, 159, 0, 90],
+                [0, 114, 178, 90],
+            ],
+        }
+    }
+
+    /// Inks on old paper, like a fantasy map
+    pub fn parchment() -> Self {
+        Self {
+            name: "parchment".to_string(),
+            background: [236, 222, 185, 255],
+            building: [122, 86, 58, 255],
+            important_building: [150, 40, 35, 255],
+            selected_building: [40, 30, 20, 255],
+            door: [60, 40, 20, 255],
+            road: [196, 165, 112, 255],
+            route: [170, 30, 30, 255],
+            grid: [110, 90, 60, 50],
+            districts: vec![
+                [150, 170, 100, 60],
+                [190, 140, 90, 60],
+                [120, 150, 170, 60],
+                [170, 120, 150, 60],
+                [200, 180, 90, 60],
+            ],
+        }
+    }
+
+    /// Themes coming with the explorer
+    pub fn builtin() -> Vec<Self> {
+        vec![Self::classic(), Self::colorblind(), Self::parchment()]
+    }
+
+    /// Built-in themes followed by the ones of a JSON file holding a list of themes, a theme of
+    /// the file replacing the built-in one of the same name. Without the file, only the built-in
+    /// themes are returned.
+    pub fn load_all(path: &Path) -> io::Result<Vec<Self>> {
+        let mut themes = Self::builtin();
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(themes),
+            Err(e) => return Err(e),
+        };
+        let loaded: Vec<Self> = serde_json::from_str(&json)?;
+        log::info!("Loaded {} themes from {}", loaded.len(), path.display());
+        for theme in loaded {
+            match themes.iter_mut().find(|t| t.name == theme.name) {
+                Some(existing) => *existing = theme,
+                None => themes.push(theme),
+            }
+        }
+        Ok(themes)
+    }
+}
+
+/// Color `over` drawn on top of `under` according to its alpha
+#[allow(clippy::cast_possible_truncation)]
+pub fn blend(under: [u8; 4], over: [u8; 4]) -> [u8; 4] {
+    let alpha = u16::from(over[3]);
+    let mix = |u: u8, o: u8| ((u16::from(o) * alpha + u16::from(u) * (255 - alpha)) / 255) as u8;
+    [
+        mix(under[0], over[0]),
+        mix(under[1], over[1]),
+        mix(under[2], over[2]),
+        under[3].max(over[3]),
+    ]
+}
@@ -0,0 +1,255 @@
+use std::{collections::HashMap, ops::Range};
+
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+
+/// Width and height of a tile, in pixels
+pub const TILE_SIZE: i64 = 256;
+/// Number of tiles kept, about 48 MB
+const CAPACITY: usize = 192;
+
+/// Tile of the city at a zoom: the bits of the zoom, and the column and row of the tile, tile
+/// `(x, y)` covering the pixels `x * TILE_SIZE..(x + 1) * TILE_SIZE` of the city drawn at this
+/// zoom
+pub type TileKey = (u32, i64, i64);
+
+struct Tile {
+    pixels: Vec<u8>,
+    /// Frame the tile was last used in, the oldest tiles being dropped first
+    last_used: u64,
+}
+
+/// Rasterized tiles of the city, so moving the camera around only copies pixels
+#[derive(Default)]
+pub struct TileCache {
+    tiles: HashMap<TileKey, Tile>,
+    frame: u64,
+}
+
+/// Pixels of the frame, `width` pixels wide
+pub struct Frame<'a> {
+    pub pixels: &'a mut [u8],
+    pub width: usize,
+}
+
+impl Frame<'_> {
+    /// Render every pixel of the frame, `origin` being the pixel of the city at its top left
+    /// corner
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn fill(&mut self, origin: (i64, i64), render: impl Fn((i64, i64)) -> [u8; 4] + Sync) {
+        let width = self.width;
+        self.pixels
+            .par_chunks_mut(4)
+            .enumerate()
+            .for_each(|(i, pixel)| {
+                let (x, y) = ((i % width) as i64, (i / width) as i64);
+                pixel.copy_from_slice(&render((origin.0 + x, origin.1 + y)));
+            });
+    }
+
+    /// Move the content of the frame by `shift` pixels, leaving the uncovered pixels as they were
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn shift(&mut self, shift: (i64, i64)) {
+        let width = self.width as i64;
+        let height = (self.pixels.len() / 4 / self.width) as i64;
+        let columns = (width - shift.0.abs()).max(0) as usize * 4;
+        let (from_x, to_x) = if shift.0 < 0 {
+            (-shift.0, 0)
+        } else {
+            (0, shift.0)
+        };
+        // Rows are copied in the order that doesn't overwrite rows still to be copied
+        let rows = (0..height).filter(|y| (0..height).contains(&(y - shift.1)));
+        let rows: Box<dyn Iterator<Item = i64>> = if shift.1 > 0 {
+            Box::new(rows.rev())
+        } else {
+            Box::new(rows)
+        };
+        for y in rows {
+            let from = (((y - shift.1) * width + from_x) * 4) as usize;
+            let to = ((y * width + to_x) * 4) as usize;
+            self.pixels.copy_within(from..from + columns, to);
+        }
+    }
+}
+
+impl TileCache {
+    /// Forget every tile
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// Fill a rectangle of the frame, `origin` being the pixel of the city at the top left
+    /// corner of the frame. The missing tiles are rendered in parallel by `render`, which is
+    /// given the pixel of the city to color.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_sign_loss
+    )]
+    pub fn blit(
+        &mut self,
+        frame: &mut Frame,
+        zoom: f32,
+        origin: (i64, i64),
+        rectangle: (Range<i64>, Range<i64>),
+        render: impl Fn((i64, i64)) -> [u8; 4] + Sync,
+    ) {
+        let (columns, rows) = rectangle;
+        if columns.is_empty() || rows.is_empty() {
+            return;
+        }
+        let zoom = zoom.to_bits();
+        let tile_range = |range: &Range<i64>, origin: i64| {
+            (range.start + origin).div_euclid(TILE_SIZE)
+                ..=(range.end - 1 + origin).div_euclid(TILE_SIZE)
+        };
+        let keys = tile_range(&rows, origin.1)
+            .flat_map(|y| tile_range(&columns, origin.0).map(move |x| (zoom, x, y)))
+            .collect::<Vec<TileKey>>();
+
+        self.frame += 1;
+        let missing = keys
+            .iter()
+            .filter(|key| !self.tiles.contains_key(key))
+            .copied()
+            .collect::<Vec<_>>();
+        let rendered = missing
+            .into_par_iter()
+            .map(|key @ (_, x, y)| {
+                let mut pixels = Vec::with_capacity((TILE_SIZE * TILE_SIZE * 4) as usize);
+                for py in 0..TILE_SIZE {
+                    for px in 0..TILE_SIZE {
+                        pixels.extend(render((x * TILE_SIZE + px, y * TILE_SIZE + py)));
+                    }
+                }
+                (key, pixels)
+            })
+            .collect::<Vec<_>>();
+        for (key, pixels) in rendered {
+            self.tiles.insert(
+                key,
+                Tile {
+                    pixels,
+                    last_used: 0,
+                },
+            );
+        }
+
+        for key @ (_, tile_x, tile_y) in keys {
+            let tile = self.tiles.get_mut(&key).unwrap();
+            tile.last_used = self.frame;
+            // Part of the rectangle covered by the tile, in pixels of the frame
+            let left = columns.start.max(tile_x * TILE_SIZE - origin.0);
+            let right = columns.end.min((tile_x + 1) * TILE_SIZE - origin.0);
+            let top = rows.start.max(tile_y * TILE_SIZE - origin.1);
+            let bottom = rows.end.min((tile_y + 1) * TILE_SIZE - origin.1);
+            let length = (right - left) as usize * 4;
+            for y in top..bottom {
+                let from = (((y + origin.1 - tile_y * TILE_SIZE) * TILE_SIZE + left + origin.0
+                    - tile_x * TILE_SIZE)
+                    * 4) as usize;
+                let to = ((y * frame.width as i64 + left) * 4) as usize;
+                frame.pixels[to..to + length].copy_from_slice(&tile.pixels[from..from + length]);
+            }
+        }
+
+        if self.tiles.len() > CAPACITY {
+            let mut ages = self
+                .tiles
+                .values()
+                .map(|tile| tile.last_used)
+                .collect::<Vec<_>>();
+            ages.sort_unstable();
+            let oldest_kept = ages[ages.len() - CAPACITY];
+            self.tiles.retain(|_, tile| tile.last_used >= oldest_kept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: i64 = 40;
+    const HEIGHT: i64 = 30;
+
+    /// A different color for every pixel of the city
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn render((x, y): (i64, i64)) -> [u8; 4] {
+        [x as u8, y as u8, (x >> 8) as u8, (y >> 8) as u8]
+    }
+
+    fn rendered(origin: (i64, i64)) -> Vec<u8> {
+        let mut pixels = vec![0; (WIDTH * HEIGHT * 4) as usize];
+        Frame {
+            pixels: &mut pixels,
+            width: WIDTH as usize,
+        }
+        .fill(origin, render);
+        pixels
+    }
+
+    #[test]
+    fn blitted_tiles_match_a_fresh_render() {
+        let mut tiles = TileCache::default();
+        // Inside a tile, across the corner of four tiles, and left of and above the origin
+        for origin in [
+            (10, 20),
+            (TILE_SIZE - 15, TILE_SIZE - 10),
+            (-270, -5),
+            (-3, -300),
+        ] {
+            let mut pixels = vec![0; (WIDTH * HEIGHT * 4) as usize];
+            let mut frame = Frame {
+                pixels: &mut pixels,
+                width: WIDTH as usize,
+            };
+            tiles.blit(&mut frame, 1.0, origin, (0..WIDTH, 0..HEIGHT), render);
+            assert!(pixels == rendered(origin), "{origin:?}");
+        }
+    }
+
+    #[test]
+    fn shifted_frames_match_a_fresh_render() {
+        let drawn = (TILE_SIZE - 20, -12);
+        for moved in [(0, 0), (7, 0), (0, -9), (-13, 5), (25, 17), (-39, -29)] {
+            let origin = (drawn.0 + moved.0, drawn.1 + moved.1);
+            let mut pixels = rendered(drawn);
+            let mut frame = Frame {
+                pixels: &mut pixels,
+                width: WIDTH as usize,
+            };
+            frame.shift((-moved.0, -moved.1));
+            // Only the pixels uncovered by the shift are drawn again, as the explorer does
+            let (uncovered_columns, kept_columns) = if moved.0 > 0 {
+                (WIDTH - moved.0..WIDTH, 0..WIDTH - moved.0)
+            } else {
+                (0..-moved.0, -moved.0..WIDTH)
+            };
+            let uncovered_rows = if moved.1 > 0 {
+                HEIGHT - moved.1..HEIGHT
+            } else {
+                0..-moved.1
+            };
+            let mut tiles = TileCache::default();
+            tiles.blit(
+                &mut frame,
+                1.0,
+                origin,
+                (uncovered_columns, 0..HEIGHT),
+                render,
+            );
+            tiles.blit(
+                &mut frame,
+                1.0,
+                origin,
+                (kept_columns, uncovered_rows),
+                render,
+            );
+            assert!(pixels == rendered(origin), "{moved:?}");
+        }
+    }
+}
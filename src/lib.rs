@@ -0,0 +1,25 @@
+pub mod accessibility;
+pub mod batch;
+pub mod building;
+pub mod centrality;
+pub mod city;
+pub mod city_generation;
+pub mod editing;
+pub mod export;
+pub mod graphics;
+pub mod network;
+pub mod placement;
+pub mod road_cost;
+pub mod road_network;
+pub mod scaling;
+pub mod stats;
+pub mod straighten;
+
+pub use building::Building;
+pub use city::{City, RoadTier};
+pub use city_generation::{CityGenerator, GridLayout, LayoutStrategy, Router};
+pub use network::ImportantNetwork;
+pub use placement::{DecayingNeighborPlacement, PlacementStrategy};
+pub use road_cost::{DefaultRoadCost, GoalPolicy, RoadCostModel};
+pub use road_network::RoadNetwork;
+pub use stats::CityStats;
@@ -1,18 +1,217 @@
-use std::fs;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use rand::random;
 
-use crate::{city::City, city_generation::CityGenerator};
+use city_generation::{
+    batch::{self, BatchJob},
+    export::{self, ExportFormat},
+    graphics, City, CityGenerator, CityStats, DefaultRoadCost, GoalPolicy, GridLayout,
+    ImportantNetwork, LayoutStrategy, Router,
+};
 
-mod building;
-mod city;
-mod city_generation;
-mod graphics;
+/// Layout of the generated city
+#[derive(Clone, Copy, ValueEnum)]
+enum Layout {
+    /// Organic growth around the important buildings
+    Organic,
+    /// Planned orthogonal street grid
+    Grid,
+}
+
+/// Where road searches may stop
+#[derive(Clone, Copy, ValueEnum)]
+enum Goal {
+    /// On any existing road or on the target building
+    AnyRoad,
+    /// Only on the door of the target building
+    TargetDoor,
+}
+
+/// Which important buildings are linked by a road
+#[derive(Clone, Copy, ValueEnum)]
+enum Network {
+    /// Every pair of important buildings
+    FullMesh,
+    /// Minimum spanning tree
+    Mst,
+    /// Delaunay triangulation
+    Delaunay,
+}
+
+/// Algorithm routing the roads
+#[derive(Clone, Copy, ValueEnum)]
+enum RouterKind {
+    /// A* over every cell
+    Astar,
+    /// A* over chunks, then over the cells of the chunks it went through
+    Hierarchical,
+}
+
+/// Format of the metrics of a city
+#[derive(Clone, Copy, ValueEnum)]
+enum StatsFormat {
+    Text,
+    Json,
+}
+
+/// Format of the summary of a batch
+#[derive(Clone, Copy, ValueEnum)]
+enum SummaryFormat {
+    Csv,
+    Json,
+}
+
+/// Format a city is exported to
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// One pixel per cell
+    Png,
+    /// Vector image
+    Svg,
+    /// Buildings and roads, can be loaded back
+    Json,
+    /// Buildings and roads as geographic features, in cell coordinates
+    Geojson,
+    /// Distance by road to the closest important building, as a PNG
+    Heatmap,
+}
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Without a command, a new city is generated and viewed
+    #[command(flatten)]
+    generation: GenerationArgs,
+    #[command(flatten)]
+    explorer: ExplorerArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a city and save it
+    Generate {
+        #[command(flatten)]
+        generation: GenerationArgs,
+        /// File to save the city to
+        #[arg(short, long, default_value = "output/city.json")]
+        output: PathBuf,
+    },
+    /// Explore a saved or new city
+    View {
+        #[command(flatten)]
+        source: SourceArgs,
+        #[command(flatten)]
+        explorer: ExplorerArgs,
+    },
+    /// Export a saved or new city
+    Export {
+        #[command(flatten)]
+        source: SourceArgs,
+        /// Format of the exported file
+        #[arg(short, long, value_enum, default_value_t = Format::Png)]
+        format: Format,
+        /// File to export the city to
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Print metrics of a saved or new city
+    Stats {
+        #[command(flatten)]
+        source: SourceArgs,
+        /// Format of the metrics
+        #[arg(short, long, value_enum, default_value_t = StatsFormat::Text)]
+        format: StatsFormat,
+    },
+    /// Generate many cities in parallel and summarize them
+    Batch {
+        /// Base parameters, `--seed` being the first seed of the range
+        #[command(flatten)]
+        generation: GenerationArgs,
+        /// Number of seeds to generate for each combination of parameters
+        #[arg(long, default_value_t = 10)]
+        seeds: u64,
+        /// Parameter to vary, as `NAME=VALUE,VALUE,...` with the name of a numeric option
+        /// (`buildings`, `scale-seeds`, ...), repeat it to vary several parameters over a grid
+        #[arg(long = "vary", value_parser = parse_variation)]
+        variations: Vec<(String, Vec<String>)>,
+        /// Directory to save the cities and the summary to
+        #[arg(short, long, default_value = "output/batch")]
+        output: PathBuf,
+        /// Format of the summary
+        #[arg(long, value_enum, default_value_t = SummaryFormat::Csv)]
+        summary: SummaryFormat,
+    },
+}
+
+/// Parse a variation, without its repeated values which would overwrite each other's cities
+fn parse_variation(variation: &str) -> Result<(String, Vec<String>), String> {
+    let (name, values) = variation
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE,VALUE,... but got {variation}"))?;
+    let mut unique: Vec<String> = vec![];
+    for value in values.split(',') {
+        if !unique.iter().any(|v| v == value) {
+            unique.push(value.to_string());
+        }
+    }
+    Ok((name.to_string(), unique))
+}
+
+/// Parse a scale, which must be a finite number above 0
+fn parse_scale(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(scale) if scale.is_finite() && scale > 0.0 => Ok(scale),
+        Ok(_) => Err(format!("the scale must be above 0, got {value}")),
+        Err(err) => Err(format!("invalid scale {value}: {err}")),
+    }
+}
+
+/// Where the city comes from
+#[derive(Args)]
+struct SourceArgs {
+    /// Load the city from a file saved by `generate` instead of generating one
+    #[arg(long)]
+    input: Option<PathBuf>,
+    #[command(flatten)]
+    generation: GenerationArgs,
+}
+
+/// Settings of the explorer
+#[derive(Args)]
+struct ExplorerArgs {
+    /// JSON file with a list of extra color themes, added to the built-in ones
+    #[arg(long, default_value = "themes.json")]
+    themes: PathBuf,
+    /// Name of the theme to start with
+    #[arg(long)]
+    theme: Option<String>,
+    /// How many times larger than the window the posters are
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=i64::from(graphics::MAX_POSTER_SCALE)))]
+    poster_scale: u32,
+}
+
+impl ExplorerArgs {
+    /// Themes to cycle through, starting with the chosen one
+    fn themes(&self) -> io::Result<Vec<graphics::Theme>> {
+        let mut themes = graphics::Theme::load_all(&self.themes)?;
+        if let Some(name) = &self.theme {
+            let i = themes.iter().position(|t| &t.name == name).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("unknown theme {name}"))
+            })?;
+            themes.rotate_left(i);
+        }
+        Ok(themes)
+    }
+}
+
+#[derive(Args, Clone)]
+struct GenerationArgs {
     /// Number of buildings
     #[arg(short, long, default_value_t = 500)]
     buildings: usize,
@@ -22,50 +221,325 @@ struct Cli {
     /// Maximum distance between important buildings
     #[arg(short, long, default_value_t = 500)]
     max_distance_seeds: i32,
-    /// Scale of the important buildings
-    #[arg(short, long, default_value_t = 1)]
-    scale_seeds: i32,
+    /// Smallest distance between a new building and the one it is placed next to, the largest
+    /// being five times as much
+    #[arg(long, default_value_t = 20)]
+    spacing: i32,
+    /// Scale of the important buildings, any positive factor
+    #[arg(short, long, default_value_t = 1.0, value_parser = parse_scale)]
+    scale_seeds: f64,
     /// Seed
     #[arg(long)]
     seed: Option<u64>,
+    /// Layout of the city
+    #[arg(long, value_enum, default_value_t = Layout::Organic)]
+    layout: Layout,
+    /// Size of the blocks of a grid city
+    #[arg(long, default_value_t = 40)]
+    block_size: i32,
+    /// Width of the streets of a grid city
+    #[arg(long, default_value_t = 2)]
+    street_width: i32,
+    /// Add diagonal avenues to a grid city
+    #[arg(long)]
+    diagonal_avenues: bool,
+    /// Extra cost of a turn when routing roads, 0 to disable
+    #[arg(long, default_value_t = 0)]
+    turn_penalty: i32,
+    /// Route roads favoring long straight streets (overrides the turn penalty)
+    #[arg(long)]
+    straight_roads: bool,
+    /// Straighten the stair-stepped parts of the roads
+    #[arg(long)]
+    straighten: bool,
+    /// Where road searches may stop
+    #[arg(long, value_enum, default_value_t = Goal::AnyRoad)]
+    goal: Goal,
+    /// Which important buildings are linked by a road
+    #[arg(long, value_enum, default_value_t = Network::FullMesh)]
+    network: Network,
+    /// Route the roads between important buildings in parallel, each one ignoring the others
+    #[arg(long)]
+    parallel_important_roads: bool,
+    /// Algorithm routing the roads
+    #[arg(long, value_enum, default_value_t = RouterKind::Astar)]
+    router: RouterKind,
+    /// Size of the chunks of the hierarchical router
+    #[arg(long, default_value_t = 16)]
+    chunk_size: i32,
+    /// Share of the roads, the busiest ones, to widen into main streets
+    #[arg(long)]
+    main_streets: Option<f64>,
 }
 
 #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-fn city_generator(cli: &Cli) -> City {
-    let buildings = cli.buildings;
-    let important_buildings = cli.important_buildings;
+fn generator(cli: &GenerationArgs, seed: u64) -> CityGenerator {
     let important_buildings_max_distance = cli.max_distance_seeds;
-    let important_buildings_scale = cli.scale_seeds;
-    let seed = cli.seed.unwrap_or_else(random);
 
-    let mut city_gen = CityGenerator::new(
+    let layout = match cli.layout {
+        Layout::Organic => LayoutStrategy::Organic,
+        Layout::Grid => LayoutStrategy::Grid(GridLayout {
+            block_size: cli.block_size,
+            street_width: cli.street_width,
+            diagonal_avenues: cli.diagonal_avenues,
+        }),
+    };
+
+    let road_cost = if cli.straight_roads {
+        DefaultRoadCost::straight()
+    } else {
+        DefaultRoadCost {
+            turn_penalty: cli.turn_penalty,
+            ..DefaultRoadCost::default()
+        }
+    };
+
+    CityGenerator::new(
         seed,
         8..30,
         8..30,
-        20..100,
+        cli.spacing..cli.spacing * 5,
         important_buildings_max_distance,
-    );
+    )
+    .with_layout(layout)
+    .with_road_cost(Box::new(road_cost))
+    .with_straightened_roads(cli.straighten)
+    .with_goal_policy(match cli.goal {
+        Goal::AnyRoad => GoalPolicy::AnyRoad,
+        Goal::TargetDoor => GoalPolicy::TargetDoor,
+    })
+    .with_important_network(match cli.network {
+        Network::FullMesh => ImportantNetwork::FullMesh,
+        Network::Mst => ImportantNetwork::MinimumSpanningTree,
+        Network::Delaunay => ImportantNetwork::Delaunay,
+    })
+    .with_parallel_important_roads(cli.parallel_important_roads)
+    .with_router(match cli.router {
+        RouterKind::Astar => Router::AStar,
+        RouterKind::Hierarchical => Router::Hierarchical {
+            chunk_size: cli.chunk_size,
+        },
+    })
+    .with_main_streets(cli.main_streets)
+}
 
-    let city = city_gen.generate(buildings, important_buildings, important_buildings_scale);
-    println!("Seed is {seed}",);
+fn city_generator(cli: &GenerationArgs) -> City {
+    let buildings = cli.buildings;
+    let important_buildings = cli.important_buildings;
+    let important_buildings_scale = cli.scale_seeds;
+    let seed = cli.seed.unwrap_or_else(random);
 
-    // let mut img = ImageBuffer::new(
-    //     10 + (city.max_x - city.min_x) as u32,
-    //     10 + (city.max_y - city.min_y) as u32,
-    // );
-    println!(
-        "size : {}x{}",
+    let city =
+        generator(cli, seed).generate(buildings, important_buildings, important_buildings_scale);
+    // Logged rather than printed so the output of `stats` can be parsed
+    log::info!("Seed is {seed}");
+    log::info!(
+        "Size is {}x{}",
         city.max_x - city.min_x,
         city.max_y - city.min_y
     );
     city
+}
 
-    // img.save("output/city.png")
+/// Load the city from its file, or generate it
+fn load_or_generate(source: &SourceArgs) -> io::Result<City> {
+    match &source.input {
+        Some(path) => export::load_city(path),
+        None => Ok(city_generator(&source.generation)),
+    }
 }
-#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+
+/// Create the directory a file will be written to
+fn create_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+impl GenerationArgs {
+    /// Set a numeric option from its name on the command line
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value {value} for {name}"))
+        }
+        match name {
+            "buildings" => self.buildings = parse(name, value)?,
+            "important-buildings" => self.important_buildings = parse(name, value)?,
+            "max-distance-seeds" => self.max_distance_seeds = parse(name, value)?,
+            "spacing" => self.spacing = parse(name, value)?,
+            "scale-seeds" => self.scale_seeds = parse_scale(value)?,
+            "block-size" => self.block_size = parse(name, value)?,
+            "street-width" => self.street_width = parse(name, value)?,
+            "turn-penalty" => self.turn_penalty = parse(name, value)?,
+            "chunk-size" => self.chunk_size = parse(name, value)?,
+            "main-streets" => self.main_streets = Some(parse(name, value)?),
+            _ => return Err(format!("{name} can't be varied")),
+        }
+        Ok(())
+    }
+}
+
+/// Every combination of the variations applied to the base parameters, with their description
+fn parameter_grid(
+    base: &GenerationArgs,
+    variations: &[(String, Vec<String>)],
+) -> Result<Vec<(String, GenerationArgs)>, String> {
+    let mut grid = vec![(String::new(), base.clone())];
+    for (name, values) in variations {
+        let mut next = vec![];
+        for (description, args) in &grid {
+            for value in values {
+                let mut args = args.clone();
+                args.set(name, value)?;
+                let separator = if description.is_empty() { "" } else { "_" };
+                next.push((format!("{description}{separator}{name}={value}"), args));
+            }
+        }
+        grid = next;
+    }
+    if variations.is_empty() {
+        grid[0].0 = "default".to_string();
+    }
+    Ok(grid)
+}
+
+fn run_batch(
+    generation: &GenerationArgs,
+    seeds: u64,
+    variations: &[(String, Vec<String>)],
+    output: &Path,
+    summary: SummaryFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let grid = parameter_grid(generation, variations)?;
+    let first_seed = generation.seed.unwrap_or(0);
+    let end_seed = first_seed
+        .checked_add(seeds)
+        .ok_or_else(|| format!("{seeds} seeds from {first_seed} go past the largest seed"))?;
+    let jobs = grid
+        .iter()
+        .flat_map(|(description, args)| {
+            (first_seed..end_seed).map(|seed| BatchJob {
+                seed,
+                parameters: description.clone(),
+                buildings: args.buildings,
+                important_buildings: args.important_buildings,
+                scale: args.scale_seeds,
+            })
+        })
+        .collect::<Vec<_>>();
+    let args_of = |job: &BatchJob| {
+        &grid
+            .iter()
+            .find(|(description, _)| *description == job.parameters)
+            .expect("jobs are built from the grid")
+            .1
+    };
+
+    let summaries = batch::run_batch(&jobs, output, |job| generator(args_of(job), job.seed))?;
+    let (file, content) = match summary {
+        SummaryFormat::Csv => ("summary.csv", batch::summary_csv(&summaries)),
+        SummaryFormat::Json => ("summary.json", batch::summary_json(&summaries)),
+    };
+    let path = output.join(file);
+    fs::write(&path, content)?;
+    println!(
+        "Generated {} cities, summary in {}",
+        jobs.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Explore a saved or new city, generating new ones from the explorer with the parameters it
+/// changes
+fn explore(
+    input: Option<&Path>,
+    generation: &GenerationArgs,
+    explorer: &ExplorerArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let themes = explorer.themes()?;
+    let mut generation = generation.clone();
+    let seed = *generation.seed.get_or_insert_with(random);
+    let city = match input {
+        Some(path) => export::load_city(path)?,
+        None => city_generator(&generation),
+    };
+    let parameters = graphics::Parameters {
+        seed,
+        buildings: generation.buildings,
+        spacing: generation.spacing,
+        scale: generation.scale_seeds,
+    };
+    let regenerator = graphics::Regenerator::new(parameters, input.is_none(), move |parameters| {
+        let mut args = generation.clone();
+        args.buildings = parameters.buildings;
+        args.spacing = parameters.spacing;
+        args.scale_seeds = parameters.scale;
+        generator(&args, parameters.seed).generate(
+            args.buildings,
+            args.important_buildings,
+            args.scale_seeds,
+        )
+    });
+    graphics::start_city_explorer(city, themes, Some(regenerator), explorer.poster_scale)?;
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    match cli.command {
+        None => explore(None, &cli.generation, &cli.explorer)?,
+        Some(Command::Generate { generation, output }) => {
+            let city = city_generator(&generation);
+            create_parent_dir(&output)?;
+            export::save_city(&city, &output)?;
+            println!("Saved to {}", output.display());
+        }
+        Some(Command::View { source, explorer }) => {
+            explore(source.input.as_deref(), &source.generation, &explorer)?;
+        }
+        Some(Command::Export {
+            source,
+            format,
+            output,
+        }) => {
+            let city = load_or_generate(&source)?;
+            let format = match format {
+                Format::Png => ExportFormat::Png,
+                Format::Svg => ExportFormat::Svg,
+                Format::Json => ExportFormat::Json,
+                Format::Geojson => ExportFormat::GeoJson,
+                Format::Heatmap => ExportFormat::Heatmap,
+            };
+            create_parent_dir(&output)?;
+            export::export_city(&city, format, &output)?;
+            println!("Exported to {}", output.display());
+        }
+        Some(Command::Stats { source, format }) => {
+            let stats = CityStats::new(&load_or_generate(&source)?);
+            match format {
+                StatsFormat::Text => println!("{stats}"),
+                StatsFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+            }
+        }
+        Some(Command::Batch {
+            generation,
+            seeds,
+            variations,
+            output,
+            summary,
+        }) => run_batch(&generation, seeds, &variations, &output, summary)?,
+    }
+    Ok(())
+}
+
 fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     let cli = Cli::parse();
-    fs::create_dir("output").unwrap_or_default();
-    let city = city_generator(&cli);
-    graphics::start_city_explorer(city);
+    if let Err(err) = run(cli) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,209 @@
+/// Which pairs of important buildings are linked by a road
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImportantNetwork {
+    /// Every pair of important buildings
+    #[default]
+    FullMesh,
+    /// Only the pairs needed to connect all of them with the shortest total distance
+    MinimumSpanningTree,
+    /// Neighbors in the Delaunay triangulation of their centers
+    Delaunay,
+}
+
+impl ImportantNetwork {
+    /// Pairs of points to link, each unordered pair appearing once as `(i, j)` with `i < j`,
+    /// sorted so the result doesn't depend on how it was computed
+    pub fn edges(self, points: &[(f64, f64)]) -> Vec<(usize, usize)> {
+        let mut edges = match self {
+            ImportantNetwork::FullMesh => full_mesh(points.len()),
+            ImportantNetwork::MinimumSpanningTree => minimum_spanning_tree(points),
+            ImportantNetwork::Delaunay => {
+                // Degenerate triangulations (aligned points) may miss some links, the spanning
+                // tree, which is part of any Delaunay triangulation, keeps everything connected
+                let mut edges = delaunay(points);
+                edges.extend(minimum_spanning_tree(points));
+                edges
+            }
+        };
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+}
+
+fn squared_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+fn ordered(i: usize, j: usize) -> (usize, usize) {
+    (i.min(j), i.max(j))
+}
+
+fn full_mesh(n: usize) -> Vec<(usize, usize)> {
+    (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .collect()
+}
+
+/// Prim's algorithm on the complete graph of the points
+fn minimum_spanning_tree(points: &[(f64, f64)]) -> Vec<(usize, usize)> {
+    if points.is_empty() {
+        return vec![];
+    }
+    let mut in_tree = vec![false; points.len()];
+    // Closest point of the tree and its distance, for every point outside of it
+    let mut closest = vec![(0, f64::INFINITY); points.len()];
+    let mut edges = vec![];
+    let mut current = 0;
+    in_tree[0] = true;
+    for _ in 1..points.len() {
+        for (i, point) in points.iter().enumerate() {
+            let distance = squared_distance(points[current], *point);
+            if !in_tree[i] && distance < closest[i].1 {
+                closest[i] = (current, distance);
+            }
+        }
+        let next = (0..points.len())
+            .filter(|&i| !in_tree[i])
+            .min_by(|&a, &b| closest[a].1.total_cmp(&closest[b].1))
+            .unwrap();
+        in_tree[next] = true;
+        edges.push(ordered(closest[next].0, next));
+        current = next;
+    }
+    edges
+}
+
+/// Check if `p` lies strictly inside the circumcircle of the triangle `(a, b, c)`
+fn in_circumcircle(a: (f64, f64), b: (f64, f64), c: (f64, f64), p: (f64, f64)) -> bool {
+    let (ax, ay) = (a.0 - p.0, a.1 - p.1);
+    let (bx, by) = (b.0 - p.0, b.1 - p.1);
+    let (cx, cy) = (c.0 - p.0, c.1 - p.1);
+    let determinant = (ax * ax + ay * ay) * (bx * cy - cx * by)
+        - (bx * bx + by * by) * (ax * cy - cx * ay)
+        + (cx * cx + cy * cy) * (ax * by - bx * ay);
+    let orientation = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    if orientation > 0.0 {
+        determinant > 0.0
+    } else {
+        determinant < 0.0
+    }
+}
+
+/// Edges of the Delaunay triangulation, Bowyer-Watson algorithm
+fn delaunay(points: &[(f64, f64)]) -> Vec<(usize, usize)> {
+    let n = points.len();
+    if n < 3 {
+        return full_mesh(n);
+    }
+
+    // Super triangle containing every point, its vertices are the indices n, n + 1 and n + 2
+    let (min_x, max_x) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p.0), hi.max(p.0))
+    });
+    let (min_y, max_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p.1), hi.max(p.1))
+    });
+    let span = (max_x - min_x).max(max_y - min_y).max(1.0) * 20.0;
+    let (mid_x, mid_y) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+    let mut vertices = points.to_vec();
+    vertices.push((mid_x - span, mid_y - span));
+    vertices.push((mid_x + span, mid_y - span));
+    vertices.push((mid_x, mid_y + span));
+
+    let mut triangles = vec![(n, n + 1, n + 2)];
+    for (i, &point) in points.iter().enumerate() {
+        let (bad, good): (Vec<_>, Vec<_>) = triangles
+            .into_iter()
+            .partition(|&(a, b, c)| in_circumcircle(vertices[a], vertices[b], vertices[c], point));
+
+        // The boundary of the hole left by the bad triangles is made of their unshared edges
+        let bad_edges = bad
+            .iter()
+            .flat_map(|&(a, b, c)| [ordered(a, b), ordered(b, c), ordered(c, a)])
+            .collect::<Vec<_>>();
+        triangles = good;
+        for &edge in &bad_edges {
+            if bad_edges.iter().filter(|&&other| other == edge).count() == 1 {
+                triangles.push((edge.0, edge.1, i));
+            }
+        }
+    }
+
+    triangles
+        .into_iter()
+        .flat_map(|(a, b, c)| [ordered(a, b), ordered(b, c), ordered(c, a)])
+        .filter(|&(a, b)| a < n && b < n)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scattered points, a few of them aligned
+    const POINTS: [(f64, f64); 8] = [
+        (0.0, 0.0),
+        (10.0, 1.0),
+        (3.0, 7.0),
+        (-4.0, 5.0),
+        (8.0, 9.0),
+        (1.0, -6.0),
+        (12.0, -3.0),
+        (6.0, 3.0),
+    ];
+
+    /// Check if the edges link every point together
+    fn is_connected(n: usize, edges: &[(usize, usize)]) -> bool {
+        let mut reached = vec![false; n];
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            if std::mem::replace(&mut reached[i], true) {
+                continue;
+            }
+            for &(a, b) in edges {
+                if a == i {
+                    stack.push(b);
+                } else if b == i {
+                    stack.push(a);
+                }
+            }
+        }
+        reached.into_iter().all(|r| r)
+    }
+
+    #[test]
+    fn full_mesh_links_every_pair_once() {
+        let edges = ImportantNetwork::FullMesh.edges(&POINTS);
+        assert_eq!(edges.len(), POINTS.len() * (POINTS.len() - 1) / 2);
+        assert!(edges.iter().all(|&(i, j)| i < j));
+    }
+
+    #[test]
+    fn minimum_spanning_tree_has_n_minus_one_edges() {
+        for n in 1..=POINTS.len() {
+            let edges = ImportantNetwork::MinimumSpanningTree.edges(&POINTS[..n]);
+            assert_eq!(edges.len(), n - 1);
+            assert!(is_connected(n, &edges));
+        }
+        assert!(ImportantNetwork::MinimumSpanningTree.edges(&[]).is_empty());
+    }
+
+    #[test]
+    fn delaunay_contains_the_minimum_spanning_tree() {
+        let tree = ImportantNetwork::MinimumSpanningTree.edges(&POINTS);
+        let triangulation = delaunay(&POINTS);
+        assert!(tree.iter().all(|edge| triangulation.contains(edge)));
+        let edges = ImportantNetwork::Delaunay.edges(&POINTS);
+        // A planar triangulation has at most 3n - 6 edges
+        assert!(edges.len() >= POINTS.len() - 1 && edges.len() <= 3 * POINTS.len() - 6);
+        assert!(is_connected(POINTS.len(), &edges));
+    }
+
+    #[test]
+    fn delaunay_of_aligned_points_stays_connected() {
+        let points = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)];
+        let edges = ImportantNetwork::Delaunay.edges(&points);
+        assert!(is_connected(points.len(), &edges));
+    }
+}
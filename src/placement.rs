@@ -0,0 +1,80 @@
+use std::ops::Range;
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{building::Building, city::City};
+
+/// Decides where the next normal building of an organic city should go.
+///
+/// The generator keeps asking for candidates until it gets one that doesn't overlap anything, so
+/// a strategy only has to propose plausible buildings, not valid ones.
+pub trait PlacementStrategy: Send + Sync {
+    /// Propose a candidate building, `remaining` buildings out of `total` are still to be placed
+    fn propose(
+        &mut self,
+        city: &City,
+        rng: &mut ChaCha8Rng,
+        remaining: usize,
+        total: usize,
+    ) -> Building;
+}
+
+/// Default strategy: spawn next to a random existing building, the distance between them
+/// decreasing as the city fills up
+#[derive(Clone, Debug)]
+pub struct DecayingNeighborPlacement {
+    /// Min and max width of the buildings
+    pub width_bound: Range<i32>,
+    /// Min and max height of the buildings
+    pub height_bound: Range<i32>,
+    /// Min and max distance between buildings
+    pub distance_bound: Range<i32>,
+}
+
+impl PlacementStrategy for DecayingNeighborPlacement {
+    fn propose(
+        &mut self,
+        city: &City,
+        rng: &mut ChaCha8Rng,
+        remaining: usize,
+        total: usize,
+    ) -> Building {
+        let Building {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } = &city.buildings()
+            [&city.building_order()[rng.random_range(0..city.building_order().len())]];
+        let x_center = x + width / 2;
+        let y_center = y + height / 2;
+
+        let distance_x = ((self.distance_bound.end - self.distance_bound.start) as f32
+            * remaining as f32
+            / total as f32) as i32
+            + self.distance_bound.start;
+
+        let distance_y = ((self.distance_bound.end - self.distance_bound.start) as f32
+            * (remaining as f32 / total as f32)) as i32
+            + self.distance_bound.start;
+
+        let spawn_x = if rng.random_bool(0.5) {
+            x_center + distance_x
+        } else {
+            x_center - distance_x
+        };
+
+        let spawn_y = if rng.random_bool(0.5) {
+            y_center + distance_y
+        } else {
+            y_center - distance_y
+        };
+
+        let width = rng.random_range(self.width_bound.clone());
+        let height = rng.random_range(self.height_bound.clone());
+
+        Building::with_random_door(rng, spawn_x, spawn_y, width, height, remaining)
+    }
+}
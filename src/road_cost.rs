@@ -0,0 +1,224 @@
+use crate::{building::Building, city::City};
+
+/// Direction of a step between two neighboring cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Heading {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Heading {
+    /// Direction going from a cell to one of its neighbors
+    pub fn between(from: (i32, i32), to: (i32, i32)) -> Option<Self> {
+        match (to.0 - from.0, to.1 - from.1) {
+            (0, -1) => Some(Heading::North),
+            (0, 1) => Some(Heading::South),
+            (1, 0) => Some(Heading::East),
+            (-1, 0) => Some(Heading::West),
+            _ => None,
+        }
+    }
+}
+
+/// State of the road search: where we are and, when turns matter, where we were going
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RoadNode {
+    /// Current cell
+    pub pos: (i32, i32),
+    /// Direction of the last step, always `None` if the cost model doesn't track headings
+    pub heading: Option<Heading>,
+}
+
+/// What the road router finds on a cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellKind {
+    /// Nothing built yet
+    Empty,
+    /// Existing road
+    Road,
+    /// Door of a building
+    Door,
+    /// Any other part of a building
+    Wall,
+}
+
+/// Where a road search is allowed to stop
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GoalPolicy {
+    /// Stop on the first existing road met, or on the target building
+    #[default]
+    AnyRoad,
+    /// Only stop on the door of the target building
+    TargetDoor,
+}
+
+/// Movement costs used to route roads through the city
+pub trait RoadCostModel: Send + Sync {
+    /// Cost of stepping from `from` to its neighbor `to`, `None` if `to` can't be walked on
+    fn step_cost(
+        &self,
+        city: &City,
+        from: (i32, i32),
+        to: (i32, i32),
+        kind: CellKind,
+    ) -> Option<i32>;
+
+    /// Whether turns have a cost, in which case the heading is part of the search state
+    fn tracks_heading(&self) -> bool {
+        false
+    }
+
+    /// Extra cost of changing direction, only used if `tracks_heading` is true
+    fn turn_cost(&self, _from: Heading, _to: Heading) -> i32 {
+        0
+    }
+
+    /// Lower bound of the cost of a single step, turns excluded
+    fn min_step_cost(&self) -> i32 {
+        1
+    }
+
+    /// Estimation of the remaining cost from a cell to the goal.
+    ///
+    /// The default is the Manhattan distance to the goal times the cheapest step, which never
+    /// overestimates the cost to reach the target. With `GoalPolicy::AnyRoad` the search may stop
+    /// earlier on a road, so it is only a guide towards the target there.
+    fn heuristic(&self, pos: (i32, i32), target: &Building, goal: GoalPolicy) -> i32 {
+        let distance = match goal {
+            GoalPolicy::AnyRoad => target.distance(pos),
+            GoalPolicy::TargetDoor => (pos.0 - target.door.0).abs() + (pos.1 - target.door.1).abs(),
+        };
+        distance * self.min_step_cost()
+    }
+}
+
+/// Default costs: cheap on roads and doors, expensive elsewhere, impossible through walls
+#[derive(Clone, Debug)]
+pub struct DefaultRoadCost {
+    /// Cost of a step on an existing road or a door
+    pub road: i32,
+    /// Cost of a step on an empty cell
+    pub empty: i32,
+    /// Extra cost when changing direction, 0 to disable
+    pub turn_penalty: i32,
+}
+
+impl Default for DefaultRoadCost {
+    fn default() -> Self {
+        Self {
+            road: 10,
+            empty: 50,
+            turn_penalty: 0,
+        }
+    }
+}
+
+impl DefaultRoadCost {
+    /// Default costs with a turn penalty high enough to favor long straight streets
+    pub fn straight() -> Self {
+        Self {
+            turn_penalty: 40,
+            ..Self::default()
+        }
+    }
+}
+
+impl RoadCostModel for DefaultRoadCost {
+    fn step_cost(
+        &self,
+        _city: &City,
+        _from: (i32, i32),
+        _to: (i32, i32),
+        kind: CellKind,
+    ) -> Option<i32> {
+        match kind {
+            CellKind::Road | CellKind::Door => Some(self.road),
+            CellKind::Empty => Some(self.empty),
+            CellKind::Wall => None,
+        }
+    }
+
+    fn tracks_heading(&self) -> bool {
+        self.turn_penalty > 0
+    }
+
+    fn min_step_cost(&self) -> i32 {
+        self.road.min(self.empty)
+    }
+
+    fn turn_cost(&self, from: Heading, to: Heading) -> i32 {
+        if from == to {
+            0
+        } else {
+            self.turn_penalty
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CityGenerator;
+
+    /// Number of changes of direction along a road
+    fn turns(road: &[(i32, i32)]) -> usize {
+        let headings = road
+            .windows(2)
+            .map(|step| Heading::between(step[0], step[1]).unwrap())
+            .collect::<Vec<_>>();
+        headings.windows(2).filter(|h| h[0] != h[1]).count()
+    }
+
+    #[test]
+    fn turns_only_cost_when_the_heading_changes() {
+        assert_eq!(Heading::between((0, 0), (0, -1)), Some(Heading::North));
+        assert_eq!(Heading::between((0, 0), (-1, 0)), Some(Heading::West));
+        assert_eq!(Heading::between((0, 0), (1, 1)), None);
+
+        let cost = DefaultRoadCost::straight();
+        assert!(cost.tracks_heading());
+        assert_eq!(cost.turn_cost(Heading::East, Heading::East), 0);
+        assert_eq!(
+            cost.turn_cost(Heading::East, Heading::South),
+            cost.turn_penalty
+        );
+        assert!(!DefaultRoadCost::default().tracks_heading());
+    }
+
+    #[test]
+    fn turn_penalty_straightens_the_roads() {
+        let building = |x, y, door| Building {
+            door,
+            x,
+            y,
+            width: 6,
+            height: 6,
+            is_important: true,
+            id: 0,
+        };
+        let (start, end) = (building(0, 0, (6, 3)), building(60, 40, (63, 40)));
+        let mut city = City::new();
+        city.insert_building(start.clone());
+        city.insert_building(end.clone());
+        city.update_borders();
+
+        let road = |cost: DefaultRoadCost| {
+            CityGenerator::new(0, 8..30, 8..30, 20..100, 500)
+                .with_goal_policy(GoalPolicy::TargetDoor)
+                .with_road_cost(Box::new(cost))
+                .generate_road(&city, &start, &end)
+                .unwrap()
+                .0
+        };
+        let (free, straight) = (
+            road(DefaultRoadCost::default()),
+            road(DefaultRoadCost::straight()),
+        );
+        // Both are shortest paths, but going east then south only turns once
+        assert_eq!(straight.len(), free.len());
+        assert_eq!(turns(&straight), 1);
+        assert!(turns(&free) > turns(&straight));
+    }
+}
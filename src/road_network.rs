@@ -0,0 +1,221 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
+
+use pathfinding::prelude::astar;
+
+use crate::{building::Building, city::City};
+
+/// Link two cells both ways, once
+fn link(neighbors: &mut HashMap<(i32, i32), Vec<(i32, i32)>>, a: (i32, i32), b: (i32, i32)) {
+    for (from, to) in [(a, b), (b, a)] {
+        let list = neighbors.entry(from).or_default();
+        if !list.contains(&to) {
+            list.push(to);
+        }
+    }
+}
+
+/// Roads of a city as a graph of cells.
+///
+/// Two cells are linked when they follow each other on a road, so parallel lanes stay apart. The
+/// ends of the roads and the doors that no road starts from are also linked to the road cells next
+/// to them.
+pub struct RoadNetwork {
+    neighbors: HashMap<(i32, i32), Vec<(i32, i32)>>,
+    doors: HashSet<(i32, i32)>,
+}
+
+impl RoadNetwork {
+    pub fn new(city: &City) -> Self {
+        let mut neighbors: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+        for road in city.roads() {
+            if let [cell] = road.as_slice() {
+                neighbors.entry(*cell).or_default();
+            }
+            for step in road.windows(2) {
+                link(&mut neighbors, step[0], step[1]);
+            }
+        }
+
+        let doors = city
+            .buildings()
+            .values()
+            .map(|b| b.door)
+            .collect::<HashSet<_>>();
+        // Roads may stop next to another one instead of on it
+        let ends = city
+            .roads()
+            .iter()
+            .flat_map(|road| road.first().into_iter().chain(road.last()))
+            .filter(|cell| !doors.contains(cell))
+            .copied()
+            .collect::<Vec<_>>();
+        for (x, y) in ends {
+            for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if neighbors.contains_key(&next) {
+                    link(&mut neighbors, (x, y), next);
+                }
+            }
+        }
+        for &(x, y) in &doors {
+            if neighbors.contains_key(&(x, y)) {
+                continue;
+            }
+            for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if neighbors.contains_key(&next) {
+                    link(&mut neighbors, (x, y), next);
+                }
+            }
+        }
+        Self { neighbors, doors }
+    }
+
+    /// Check if a cell is part of the network
+    pub fn contains(&self, cell: (i32, i32)) -> bool {
+        self.neighbors.contains_key(&cell)
+    }
+
+    /// Cells linked to `cell`
+    pub fn neighbors(&self, cell: (i32, i32)) -> &[(i32, i32)] {
+        self.neighbors.get(&cell).map_or(&[], Vec::as_slice)
+    }
+
+    /// All the cells of the network
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.neighbors.keys().copied()
+    }
+
+    /// Number of steps from the closest source to every cell reachable from the sources
+    pub fn distances(
+        &self,
+        sources: impl IntoIterator<Item = (i32, i32)>,
+    ) -> HashMap<(i32, i32), usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        for source in sources {
+            if self.contains(source) && distances.insert(source, 0).is_none() {
+                queue.push_back(source);
+            }
+        }
+        while let Some(cell) = queue.pop_front() {
+            let distance = distances[&cell];
+            for &next in self.neighbors(cell) {
+                if let Entry::Vacant(entry) = distances.entry(next) {
+                    entry.insert(distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Intersections, each made of the adjacent cells where at least three roads meet, doors
+    /// not counting as roads
+    pub fn intersections(&self) -> Vec<Vec<(i32, i32)>> {
+        let junctions = self
+            .neighbors
+            .iter()
+            .filter(|(cell, neighbors)| {
+                !self.doors.contains(cell)
+                    && neighbors.iter().filter(|n| !self.doors.contains(n)).count() >= 3
+            })
+            .map(|(&cell, _)| cell)
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        let mut intersections = vec![];
+        for &start in &junctions {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut intersection = vec![start];
+            let mut i = 0;
+            while i < intersection.len() {
+                let (x, y) = intersection[i];
+                for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                    if junctions.contains(&next) && seen.insert(next) {
+                        intersection.push(next);
+                    }
+                }
+                i += 1;
+            }
+            intersections.push(intersection);
+        }
+        intersections
+    }
+
+    /// Road cells leading nowhere: linked to a single cell, and not a door
+    pub fn dead_ends(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.neighbors
+            .iter()
+            .filter(|(cell, neighbors)| neighbors.len() == 1 && !self.doors.contains(cell))
+            .map(|(&cell, _)| cell)
+    }
+
+    /// Shortest route between two cells of the network, with its number of steps
+    pub fn route(&self, from: (i32, i32), to: (i32, i32)) -> Option<(Vec<(i32, i32)>, usize)> {
+        if !self.contains(from) || !self.contains(to) {
+            return None;
+        }
+        astar(
+            &from,
+            |&cell| self.neighbors(cell).iter().map(|&next| (next, 1)),
+            |&(x, y)| (x - to.0).unsigned_abs() as usize + (y - to.1).unsigned_abs() as usize,
+            |&cell| cell == to,
+        )
+    }
+
+    /// Shortest walking route from the door of a building to the door of another one, with its
+    /// number of steps
+    pub fn route_between(
+        &self,
+        from: &Building,
+        to: &Building,
+    ) -> Option<(Vec<(i32, i32)>, usize)> {
+        self.route(from.door, to.door)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::city::RoadTier;
+
+    /// A square of roads split by a street in the middle, with a spur on its right side
+    fn grid() -> RoadNetwork {
+        let mut city = City::new();
+        let roads = [
+            (0..=4).map(|x| (x, 0)).collect::<Vec<_>>(),
+            (0..=4).map(|x| (x, 4)).collect(),
+            (0..=4).map(|y| (0, y)).collect(),
+            (0..=4).map(|y| (4, y)).collect(),
+            (0..=4).map(|y| (2, y)).collect(),
+            vec![(5, 2), (6, 2)],
+        ];
+        for road in roads {
+            city.push_road(road, RoadTier::Local);
+        }
+        RoadNetwork::new(&city)
+    }
+
+    #[test]
+    fn intersections_are_where_three_roads_meet() {
+        let mut intersections = grid().intersections().concat();
+        intersections.sort_unstable();
+        assert_eq!(intersections, [(2, 0), (2, 4), (4, 2)]);
+    }
+
+    #[test]
+    fn dead_ends_are_roads_leading_nowhere() {
+        assert_eq!(grid().dead_ends().collect::<Vec<_>>(), [(6, 2)]);
+    }
+
+    #[test]
+    fn route_follows_the_roads() {
+        let network = grid();
+        let (route, steps) = network.route((0, 2), (6, 2)).unwrap();
+        assert_eq!(steps, 10);
+        assert_eq!(route.first(), Some(&(0, 2)));
+        assert_eq!(route.last(), Some(&(6, 2)));
+        assert!(route.iter().all(|&cell| network.contains(cell)));
+        assert!(network.route((0, 2), (1, 2)).is_none());
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{building::Building, city::City, city_generation::CellType, straighten::digital_line};
+
+/// Scale a coordinate, rounding to the closest cell
+#[allow(clippy::cast_possible_truncation)]
+fn scale_coordinate(value: i32, factor: f64) -> i32 {
+    (f64::from(value) * factor).round() as i32
+}
+
+/// Scale a point, rounding to the closest cell
+pub fn scale_point(pos: (i32, i32), factor: f64) -> (i32, i32) {
+    (
+        scale_coordinate(pos.0, factor),
+        scale_coordinate(pos.1, factor),
+    )
+}
+
+/// Scale a building by any positive factor.
+///
+/// Corners are scaled and rounded, so buildings sharing a wall still do after scaling, and the
+/// door, scaled the same way, stays on the wall it was on.
+pub fn scale_building(building: &Building, factor: f64) -> Building {
+    let (x, y) = scale_point((building.x, building.y), factor);
+    let (x1, y1) = scale_point(
+        (building.x + building.width, building.y + building.height),
+        factor,
+    );
+    // Walls must not collapse on each other
+    let (width, height) = ((x1 - x).max(1), (y1 - y).max(1));
+    let (door_x, door_y) = scale_point(building.door, factor);
+    Building {
+        door: (door_x.clamp(x, x + width), door_y.clamp(y, y + height)),
+        x,
+        y,
+        width,
+        height,
+        ..building.clone()
+    }
+}
+
+/// Scale a road by any positive factor.
+///
+/// Every cell is scaled then linked to the previous one by a 4-connected line, so the road stays
+/// continuous whatever the steps it was made of. Both ends are kept, and an empty road stays
+/// empty.
+pub fn resample_road(road: &[(i32, i32)], factor: f64) -> Vec<(i32, i32)> {
+    let mut resampled: Vec<(i32, i32)> = vec![];
+    for &cell in road {
+        let scaled = scale_point(cell, factor);
+        match resampled.last() {
+            None => resampled.push(scaled),
+            Some(&last) if last == scaled => {}
+            Some(&last) => resampled.extend_from_slice(&digital_line(last, scaled)[1..]),
+        }
+    }
+    resampled
+}
+
+/// Cell an end of a road was linked to: the door it is on, or a door or road cell next to it
+fn end_link(
+    end: (i32, i32),
+    inner: Option<(i32, i32)>,
+    doors: &HashMap<(i32, i32), (i32, i32)>,
+    road_cells: &HashSet<(i32, i32)>,
+) -> Option<Link> {
+    if doors.contains_key(&end) {
+        return Some(Link::Door(end));
+    }
+    let (x, y) = end;
+    [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+        .into_iter()
+        .filter(|&next| Some(next) != inner)
+        .find_map(|next| {
+            if doors.contains_key(&next) {
+                Some(Link::Door(next))
+            } else {
+                road_cells.contains(&next).then_some(Link::Road(next))
+            }
+        })
+}
+
+/// What the end of a road is linked to, before scaling
+enum Link {
+    Door((i32, i32)),
+    Road((i32, i32)),
+}
+
+/// Scale all the buildings and roads of a city, and rebuild its occupancy map from them.
+///
+/// Buildings whose corners land on the same cell are moved right until theirs is free. The ends
+/// of the roads are stretched to the doors and roads they were linked to, which rounding may
+/// have moved away from them.
+pub fn scale_city(city: &mut City, factor: f64) {
+    let mut corners = HashSet::new();
+    let scaled = city
+        .building_order()
+        .iter()
+        .map(|key| {
+            let mut building = scale_building(&city.buildings()[key], factor);
+            while !corners.insert((building.x, building.y)) {
+                building.x += 1;
+                building.door.0 += 1;
+            }
+            (*key, building)
+        })
+        .collect::<HashMap<_, _>>();
+    let doors = scaled
+        .iter()
+        .map(|(key, building)| (city.buildings()[key].door, building.door))
+        .collect::<HashMap<_, _>>();
+    let road_cells = city
+        .roads()
+        .iter()
+        .flatten()
+        .copied()
+        .collect::<HashSet<_>>();
+
+    city.map_buildings(|building| scaled[&(building.x, building.y)].clone())
+        .expect("corners were made unique");
+    let target = |link: Link| match link {
+        Link::Door(door) => doors[&door],
+        Link::Road(cell) => scale_point(cell, factor),
+    };
+    city.map_roads(|road| {
+        let mut resampled = resample_road(road, factor);
+        let (Some(&first), Some(&last)) = (road.first(), road.last()) else {
+            return resampled;
+        };
+        if let Some(link) = end_link(first, road.get(1).copied(), &doors, &road_cells) {
+            let mut start = digital_line(target(link), resampled[0]);
+            start.pop();
+            resampled.splice(0..0, start);
+        }
+        let inner = road.len().checked_sub(2).map(|i| road[i]);
+        if let Some(link) = end_link(last, inner, &doors, &road_cells) {
+            let end = resampled[resampled.len() - 1];
+            resampled.extend_from_slice(&digital_line(end, target(link))[1..]);
+        }
+        resampled
+    });
+
+    #[allow(clippy::cast_possible_truncation)]
+    let scale_border = |value: i32, round: fn(f64) -> f64| round(f64::from(value) * factor) as i32;
+    city.min_x = scale_border(city.min_x, f64::floor);
+    city.min_y = scale_border(city.min_y, f64::floor);
+    city.max_x = scale_border(city.max_x, f64::ceil);
+    city.max_y = scale_border(city.max_y, f64::ceil);
+
+    city.rebuild_occupancy();
+}
+
+/// Connectivity problems found in a city
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectivityReport {
+    /// Indices of the roads with two consecutive cells that aren't neighbors
+    pub broken_roads: Vec<usize>,
+    /// Keys of the important buildings that can't be reached from the first one by road
+    pub unreachable_important_buildings: Vec<(i32, i32)>,
+}
+
+impl ConnectivityReport {
+    /// Check if no problem was found
+    pub fn is_connected(&self) -> bool {
+        self.broken_roads.is_empty() && self.unreachable_important_buildings.is_empty()
+    }
+}
+
+/// Check that every road is continuous, and that the important buildings can all be reached from
+/// one another through roads and doors
+pub fn verify_connectivity(city: &City) -> ConnectivityReport {
+    let broken_roads = city
+        .roads()
+        .iter()
+        .enumerate()
+        .filter(|(_, road)| {
+            road.windows(2)
+                .any(|step| (step[1].0 - step[0].0).abs() + (step[1].1 - step[0].1).abs() != 1)
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    let doors = city
+        .buildings()
+        .values()
+        .map(|b| b.door)
+        .collect::<HashSet<(i32, i32)>>();
+    let walkable = |pos: &(i32, i32)| {
+        matches!(city.is_something.get(pos), Some(CellType::Road)) || doors.contains(pos)
+    };
+
+    let mut reached = HashSet::new();
+    let mut queue = VecDeque::new();
+    if let Some(first) = city.important_buildings().first() {
+        let door = city.buildings()[first].door;
+        reached.insert(door);
+        queue.push_back(door);
+    }
+    while let Some((x, y)) = queue.pop_front() {
+        for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if walkable(&next) && reached.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    let unreachable_important_buildings = city
+        .important_buildings()
+        .iter()
+        .filter(|key| !reached.contains(&city.buildings()[*key].door))
+        .copied()
+        .collect();
+
+    ConnectivityReport {
+        broken_roads,
+        unreachable_important_buildings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::city::RoadTier;
+
+    fn building(x: i32, door: (i32, i32)) -> Building {
+        Building {
+            door,
+            x,
+            y: 0,
+            width: 4,
+            height: 4,
+            is_important: true,
+            id: 0,
+        }
+    }
+
+    /// Check that every step of a road moves by one cell along a single axis
+    fn is_continuous(road: &[(i32, i32)]) -> bool {
+        road.windows(2)
+            .all(|step| (step[1].0 - step[0].0).abs() + (step[1].1 - step[0].1).abs() == 1)
+    }
+
+    /// Two important buildings facing each other, linked by a road from `x = 5` to `x = end`
+    fn two_buildings(end: i32) -> City {
+        let mut city = City::new();
+        city.insert_building(building(0, (4, 2)));
+        city.insert_building(building(10, (10, 2)));
+        city.push_road((5..=end).map(|x| (x, 2)).collect(), RoadTier::Arterial);
+        city.rebuild_occupancy();
+        city
+    }
+
+    #[test]
+    fn scale_point_rounds_to_the_closest_cell() {
+        assert_eq!(scale_point((3, -3), 0.5), (2, -2));
+        assert_eq!(scale_point((4, 7), 1.5), (6, 11));
+        assert_eq!(scale_point((10, 10), 0.33), (3, 3));
+    }
+
+    #[test]
+    fn scale_building_keeps_walls_apart_and_the_door_on_a_wall() {
+        let small = Building {
+            door: (6, 5),
+            x: 5,
+            y: 5,
+            width: 2,
+            height: 1,
+            is_important: false,
+            id: 3,
+        };
+        let scaled = scale_building(&small, 0.1);
+        assert_eq!((scaled.x, scaled.y), (1, 1));
+        assert_eq!((scaled.width, scaled.height), (1, 1));
+        assert!(scaled.contains(scaled.door));
+        assert_eq!(scaled.id, 3);
+
+        let scaled = scale_building(&building(10, (10, 2)), 2.5);
+        assert_eq!((scaled.x, scaled.width, scaled.height), (25, 10, 10));
+        assert_eq!(scaled.door, (25, 5));
+    }
+
+    #[test]
+    fn resample_road_keeps_empty_roads_empty() {
+        assert!(resample_road(&[], 3.0).is_empty());
+    }
+
+    #[test]
+    fn resample_road_keeps_diagonal_runs_continuous() {
+        let road = [(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)];
+        let resampled = resample_road(&road, 2.0);
+        assert_eq!(resampled.first(), Some(&(0, 0)));
+        assert_eq!(resampled.last(), Some(&(4, 4)));
+        assert_eq!(resampled.len(), 9);
+        assert!(is_continuous(&resampled));
+    }
+
+    #[test]
+    fn resample_road_handles_non_integer_factors() {
+        let road = [(0, 0), (1, 0), (2, 0), (3, 0), (3, 1)];
+        let grown = resample_road(&road, 1.5);
+        assert_eq!(grown.last(), Some(&(5, 2)));
+        assert!(is_continuous(&grown));
+
+        // Cells merging when shrinking aren't repeated
+        let shrunk = resample_road(&road, 0.5);
+        assert_eq!(shrunk, [(0, 0), (1, 0), (2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn verify_connectivity_accepts_a_connected_city() {
+        assert!(verify_connectivity(&two_buildings(9)).is_connected());
+    }
+
+    #[test]
+    fn verify_connectivity_finds_cut_off_buildings_and_broken_roads() {
+        let mut city = two_buildings(7);
+        let report = verify_connectivity(&city);
+        assert!(report.broken_roads.is_empty());
+        assert_eq!(report.unreachable_important_buildings, [(10, 0)]);
+
+        city.push_road(vec![(8, 2), (9, 3)], RoadTier::Local);
+        city.rebuild_occupancy();
+        let report = verify_connectivity(&city);
+        assert_eq!(report.broken_roads, [1]);
+    }
+
+    #[test]
+    fn scale_city_keeps_road_ends_on_their_doors_and_junctions() {
+        for factor in [0.5, 2.0, 2.5] {
+            let mut city = two_buildings(9);
+            // A third building, whose road stops next to the first one
+            city.insert_building(Building {
+                door: (7, 10),
+                x: 5,
+                y: 10,
+                ..building(0, (0, 0))
+            });
+            city.push_road((3..=9).rev().map(|y| (7, y)).collect(), RoadTier::Local);
+            city.rebuild_occupancy();
+            assert!(verify_connectivity(&city).is_connected());
+
+            scale_city(&mut city, factor);
+            let report = verify_connectivity(&city);
+            assert!(report.is_connected(), "{factor}: {report:?}");
+            assert_eq!(city.buildings().len(), 3);
+        }
+    }
+
+    #[test]
+    fn scale_city_moves_buildings_landing_on_the_same_corner() {
+        let mut city = City::new();
+        city.insert_building(building(0, (4, 2)));
+        city.insert_building(building(1, (5, 2)));
+        scale_city(&mut city, 0.1);
+        assert_eq!(city.buildings().len(), 2);
+        assert_eq!(city.building_order().len(), 2);
+        assert!(city
+            .building_order()
+            .iter()
+            .all(|key| city.buildings().contains_key(key)));
+    }
+
+    #[test]
+    fn map_buildings_rejects_shared_corners() {
+        let mut city = two_buildings(9);
+        let result = city.map_buildings(|b| Building { x: 0, ..b.clone() });
+        assert_eq!(result, Err((0, 0)));
+        assert_eq!(city.building_order(), [(0, 0), (10, 0)]);
+        assert_eq!(city.buildings().len(), 2);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::Serialize;
+
+use crate::{
+    city::{City, RoadTier},
+    road_network::RoadNetwork,
+};
+
+/// Smallest and largest coordinates covered by the buildings and the roads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BoundingBox {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+impl BoundingBox {
+    /// Number of cells in the box
+    pub fn area(&self) -> i64 {
+        i64::from(self.max_x - self.min_x + 1) * i64::from(self.max_y - self.min_y + 1)
+    }
+}
+
+/// Buildings whose footprint, in cells, is in `min..max`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct FootprintBin {
+    pub min: i32,
+    pub max: i32,
+    pub count: usize,
+}
+
+/// Metrics of a city
+#[derive(Clone, Debug, Serialize)]
+pub struct CityStats {
+    pub buildings: usize,
+    pub important_buildings: usize,
+    pub roads: usize,
+    /// Number of steps of all the roads
+    pub road_length: usize,
+    /// Number of steps of the roads of each tier
+    pub road_length_by_tier: BTreeMap<RoadTier, usize>,
+    pub bounding_box: BoundingBox,
+    /// Buildings per 10 000 cells of the bounding box
+    pub building_density: f64,
+    /// Share of the bounding box covered by buildings
+    pub built_ratio: f64,
+    /// Number of buildings by footprint, in bins doubling in size
+    pub footprint_histogram: Vec<FootprintBin>,
+    /// Average number of road steps from the door of a normal building to the closest important
+    /// one, among the buildings that can reach one
+    pub average_path_to_important: Option<f64>,
+    /// Normal buildings that can't reach any important building by road
+    pub unreachable_buildings: usize,
+    pub dead_ends: usize,
+    pub intersections: usize,
+}
+
+impl CityStats {
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(city: &City) -> Self {
+        let mut road_length_by_tier = BTreeMap::new();
+        for (road, tier) in city.roads_with_tiers() {
+            *road_length_by_tier.entry(tier).or_default() += road.len().saturating_sub(1);
+        }
+
+        let cells = city
+            .buildings()
+            .values()
+            .flat_map(|b| [(b.x, b.y), (b.x + b.width, b.y + b.height)])
+            .chain(city.roads().iter().flatten().copied());
+        let bounding_box = cells
+            .fold(None, |bbox: Option<BoundingBox>, (x, y)| {
+                Some(bbox.map_or(
+                    BoundingBox {
+                        min_x: x,
+                        min_y: y,
+                        max_x: x,
+                        max_y: y,
+                    },
+                    |bbox| BoundingBox {
+                        min_x: bbox.min_x.min(x),
+                        min_y: bbox.min_y.min(y),
+                        max_x: bbox.max_x.max(x),
+                        max_y: bbox.max_y.max(y),
+                    },
+                ))
+            })
+            .unwrap_or_default();
+        let area = bounding_box.area() as f64;
+
+        let footprints = city
+            .buildings()
+            .values()
+            .map(|b| (b.width + 1) * (b.height + 1))
+            .collect::<Vec<i32>>();
+        let mut bins: BTreeMap<u32, usize> = BTreeMap::new();
+        for footprint in &footprints {
+            *bins.entry(footprint.max(&1).ilog2()).or_default() += 1;
+        }
+        let footprint_histogram = bins
+            .into_iter()
+            .map(|(bin, count)| FootprintBin {
+                min: 1 << bin,
+                max: 1 << (bin + 1),
+                count,
+            })
+            .collect();
+
+        let network = RoadNetwork::new(city);
+        let distances = network.distances(
+            city.important_buildings()
+                .iter()
+                .map(|key| city.buildings()[key].door),
+        );
+        let paths = city
+            .buildings()
+            .values()
+            .filter(|b| !b.is_important)
+            .map(|b| distances.get(&b.door))
+            .collect::<Vec<_>>();
+        let reached = paths.iter().flatten().map(|&&d| d).collect::<Vec<usize>>();
+        let average_path_to_important = (!reached.is_empty())
+            .then(|| reached.iter().sum::<usize>() as f64 / reached.len() as f64);
+
+        Self {
+            buildings: city.buildings().len(),
+            important_buildings: city.important_buildings().len(),
+            roads: city.roads().len(),
+            road_length: road_length_by_tier.values().sum(),
+            road_length_by_tier,
+            bounding_box,
+            building_density: city.buildings().len() as f64 * 10_000.0 / area,
+            built_ratio: footprints.iter().map(|&f| f64::from(f)).sum::<f64>() / area,
+            footprint_histogram,
+            average_path_to_important,
+            unreachable_buildings: paths.len() - reached.len(),
+            dead_ends: network.dead_ends().count(),
+            intersections: network.intersections().len(),
+        }
+    }
+}
+
+impl fmt::Display for CityStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "buildings: {}", self.buildings)?;
+        writeln!(f, "important buildings: {}", self.important_buildings)?;
+        writeln!(f, "roads: {}", self.roads)?;
+        writeln!(f, "road length: {}", self.road_length)?;
+        for (tier, length) in &self.road_length_by_tier {
+            writeln!(f, "  {tier:?}: {length}")?;
+        }
+        let bbox = self.bounding_box;
+        writeln!(
+            f,
+            "bounding box: ({}, {}) to ({}, {}), {} cells",
+            bbox.min_x,
+            bbox.min_y,
+            bbox.max_x,
+            bbox.max_y,
+            bbox.area()
+        )?;
+        writeln!(
+            f,
+            "building density: {:.2} per 10000 cells",
+            self.building_density
+        )?;
+        writeln!(f, "built ratio: {:.2}%", self.built_ratio * 100.0)?;
+        writeln!(f, "footprints:")?;
+        for bin in &self.footprint_histogram {
+            writeln!(f, "  {}..{}: {}", bin.min, bin.max, bin.count)?;
+        }
+        match self.average_path_to_important {
+            Some(average) => writeln!(f, "average path to an important building: {average:.1}")?,
+            None => writeln!(f, "average path to an important building: none")?,
+        }
+        writeln!(f, "unreachable buildings: {}", self.unreachable_buildings)?;
+        writeln!(f, "dead ends: {}", self.dead_ends)?;
+        write!(f, "intersections: {}", self.intersections)
+    }
+}
@@ -0,0 +1,155 @@
+/// Cells of a 4-connected digital line between two points, both included.
+///
+/// It has the same length as any other monotone path between them, but spreads the steps evenly
+/// so it looks like a straight, diagonal or axis-aligned, line.
+pub fn digital_line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (nx, ny) = (dx.abs(), dy.abs());
+    let (sx, sy) = (dx.signum(), dy.signum());
+
+    let mut line = Vec::with_capacity((nx + ny + 1) as usize);
+    let mut position = from;
+    line.push(position);
+    let (mut ix, mut iy) = (0, 0);
+    while ix < nx || iy < ny {
+        // Step along the axis that keeps us the closest to the ideal line
+        if i64::from(1 + 2 * ix) * i64::from(ny) < i64::from(1 + 2 * iy) * i64::from(nx) {
+            position.0 += sx;
+            ix += 1;
+        } else {
+            position.1 += sy;
+            iy += 1;
+        }
+        line.push(position);
+    }
+    line
+}
+
+/// Direction of the steps of a monotone path along one axis, 0 until it moves along it
+#[derive(Clone, Copy, Default)]
+struct Monotony(i32, i32);
+
+impl Monotony {
+    /// Take a step, returning whether the path is still monotone
+    fn step(&mut self, from: (i32, i32), to: (i32, i32)) -> bool {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let ok_x = dx == 0 || self.0 == 0 || dx == self.0;
+        let ok_y = dy == 0 || self.1 == 0 || dy == self.1;
+        if dx != 0 {
+            self.0 = dx;
+        }
+        if dy != 0 {
+            self.1 = dy;
+        }
+        ok_x && ok_y
+    }
+}
+
+/// Replace the stair-stepped parts of a road by straight runs.
+///
+/// Monotone stretches are redrawn as digital lines, each as long as possible until a line would
+/// go over a cell that isn't walkable. Both ends of the road are kept so it stays connected to
+/// what it links, and its length doesn't change.
+pub fn straighten_road(
+    road: &[(i32, i32)],
+    is_walkable: impl Fn((i32, i32)) -> bool,
+) -> Vec<(i32, i32)> {
+    if road.len() < 3 {
+        return road.to_vec();
+    }
+    let mut straightened = vec![road[0]];
+    let mut start = 0;
+    while start < road.len() - 1 {
+        let mut best = start + 1;
+        let mut best_line = vec![road[start], road[start + 1]];
+        let mut monotony = Monotony::default();
+        monotony.step(road[start], road[start + 1]);
+        for end in start + 2..road.len() {
+            if !monotony.step(road[end - 1], road[end]) {
+                break;
+            }
+            let line = digital_line(road[start], road[end]);
+            if !line[1..line.len() - 1]
+                .iter()
+                .all(|&cell| is_walkable(cell))
+            {
+                break;
+            }
+            best = end;
+            best_line = line;
+        }
+        straightened.extend_from_slice(&best_line[1..]);
+        start = best;
+    }
+    straightened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that every step of a line moves by one cell along a single axis
+    fn is_connected(line: &[(i32, i32)]) -> bool {
+        line.windows(2)
+            .all(|step| (step[1].0 - step[0].0).abs() + (step[1].1 - step[0].1).abs() == 1)
+    }
+
+    #[test]
+    fn digital_line_keeps_its_endpoints() {
+        for to in [(0, 0), (5, 0), (0, -4), (3, 3), (-7, 2), (2, -9)] {
+            let line = digital_line((1, 1), (1 + to.0, 1 + to.1));
+            assert_eq!(line.first(), Some(&(1, 1)));
+            assert_eq!(line.last(), Some(&(1 + to.0, 1 + to.1)));
+            assert_eq!(line.len() as i32, to.0.abs() + to.1.abs() + 1);
+        }
+    }
+
+    #[test]
+    fn digital_line_is_connected_and_monotone() {
+        for to in [(6, 1), (-6, 1), (4, 4), (-3, -8)] {
+            let line = digital_line((0, 0), to);
+            assert!(is_connected(&line), "{line:?}");
+            let mut monotony = Monotony::default();
+            assert!(line.windows(2).all(|step| monotony.step(step[0], step[1])));
+        }
+    }
+
+    #[test]
+    fn digital_line_spreads_steep_slopes() {
+        let line = digital_line((0, 0), (2, 10));
+        assert!(is_connected(&line));
+        // The two horizontal steps are spread out instead of being at one end
+        let columns = line.iter().map(|&(x, _)| x).collect::<Vec<_>>();
+        let first_step = columns.iter().position(|&x| x == 1).unwrap();
+        let second_step = columns.iter().position(|&x| x == 2).unwrap();
+        assert!(first_step > 1 && second_step < line.len() - 2);
+        assert!(second_step - first_step > 3);
+    }
+
+    #[test]
+    fn straighten_road_keeps_ends_and_length() {
+        let stairs = [
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (2, 1),
+            (2, 2),
+            (3, 2),
+            (4, 2),
+            (4, 3),
+        ];
+        let road = straighten_road(&stairs, |_| true);
+        assert_eq!(road.first(), stairs.first());
+        assert_eq!(road.last(), stairs.last());
+        assert_eq!(road.len(), stairs.len());
+        assert!(is_connected(&road));
+    }
+
+    #[test]
+    fn straighten_road_avoids_unwalkable_cells() {
+        let stairs = [(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)];
+        let road = straighten_road(&stairs, |cell| cell != (1, 1));
+        assert!(!road.contains(&(1, 1)));
+        assert!(is_connected(&road));
+    }
+}